@@ -0,0 +1,330 @@
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::cpu::{Cpu, DEFAULT_PATTERN, DEFAULT_PITCH};
+use crate::random::RandomSource;
+
+const VOLUME: f32 = 0.25;
+
+// everything needed to produce sound, copied out of the cpu once per frame
+// so it can be handed to an audio thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Voice {
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+    pub playing: bool,
+    pub sample: Option<SampleVoice>,
+}
+
+// a MegaChip sample, copied out of ram when it starts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleVoice {
+    pub id: u32,
+    pub rate: u16,
+    pub looping: bool,
+    pub data: Arc<[u8]>,
+}
+
+impl Default for Voice {
+    fn default() -> Self {
+        Voice {
+            pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+            playing: false,
+            sample: None,
+        }
+    }
+}
+
+impl Voice {
+    // XO-CHIP plays the 128 pattern bits at 4000 * 2^((pitch - 64) / 48)
+    // bits per second
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0)
+    }
+
+    fn bit(&self, idx: usize) -> bool {
+        let byte = self.pattern[(idx / 8) % 16];
+        (byte << (idx % 8)) & 0x80 != 0
+    }
+}
+
+impl<R: RandomSource, const MEMORY: usize> Cpu<R, MEMORY> {
+    pub fn voice(&self) -> Voice {
+        let mut voice = Voice::default();
+        voice.update(self);
+        voice
+    }
+}
+
+impl Voice {
+    // only copies the sample data when a new sample has started
+    pub fn update<R: RandomSource, const MEMORY: usize>(&mut self, cpu: &Cpu<R, MEMORY>) {
+        self.pattern = cpu.pattern;
+        self.pitch = cpu.pitch;
+        self.playing = cpu.st > 0;
+
+        let Some(sample) = cpu.mega.as_ref().and_then(|mega| mega.sample) else {
+            self.sample = None;
+            return;
+        };
+        if self.sample.as_ref().is_some_and(|current| current.id == sample.id) {
+            return;
+        }
+        self.sample = Some(SampleVoice {
+            id: sample.id,
+            rate: sample.rate,
+            looping: sample.looping,
+            data: cpu.ram[sample.addr..sample.addr + sample.len].into(),
+        });
+    }
+}
+
+// turns a voice into samples, keeping the position within the pattern
+// between calls so consecutive buffers join up without clicks
+#[derive(Debug, Clone)]
+pub struct Synth {
+    sample_rate: u32,
+    position: f64,
+    // which sample is playing and how far into it, in samples of its own rate
+    sample_id: Option<u32>,
+    sample_position: f64,
+}
+
+impl Synth {
+    pub fn new(sample_rate: u32) -> Synth {
+        Synth {
+            sample_rate,
+            position: 0.0,
+            sample_id: None,
+            sample_position: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // fills `out` with mono samples in -1.0..=1.0: the buzzer while the
+    // sound timer runs, mixed with any MegaChip sample
+    pub fn render(&mut self, voice: &Voice, out: &mut [f32]) {
+        self.render_pattern(voice, out);
+        if let Some(sample) = &voice.sample {
+            self.mix_sample(sample, out);
+        } else {
+            self.sample_id = None;
+        }
+    }
+
+    fn render_pattern(&mut self, voice: &Voice, out: &mut [f32]) {
+        if !voice.playing {
+            out.fill(0.0);
+            self.position = 0.0;
+            return;
+        }
+
+        let step = voice.playback_rate() / self.sample_rate as f64;
+        for sample in out {
+            *sample = if voice.bit(self.position as usize) {
+                VOLUME
+            } else {
+                -VOLUME
+            };
+            self.position = (self.position + step) % 128.0;
+        }
+    }
+
+    // the samples are 8-bit unsigned pcm
+    fn mix_sample(&mut self, sample: &SampleVoice, out: &mut [f32]) {
+        if self.sample_id != Some(sample.id) {
+            self.sample_id = Some(sample.id);
+            self.sample_position = 0.0;
+        }
+
+        let len = sample.data.len() as f64;
+        let step = sample.rate as f64 / self.sample_rate as f64;
+        for out in out {
+            if self.sample_position >= len {
+                if !sample.looping || len == 0.0 {
+                    return;
+                }
+                self.sample_position %= len;
+            }
+
+            let pcm = sample.data[self.sample_position as usize];
+            *out += (pcm as f32 - 128.0) / 128.0 * VOLUME;
+            self.sample_position += step;
+        }
+    }
+}
+
+// records what a rom would play, one 60 Hz frame at a time, so the output
+// can be checked without a sound card
+#[derive(Debug, Clone)]
+pub struct OfflineRenderer {
+    synth: Synth,
+    samples: Vec<f32>,
+}
+
+impl OfflineRenderer {
+    pub fn new(sample_rate: u32) -> OfflineRenderer {
+        OfflineRenderer {
+            synth: Synth::new(sample_rate),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn record_frame(&mut self, voice: &Voice) {
+        let len = self.synth.sample_rate() as usize / 60;
+        let start = self.samples.len();
+        self.samples.resize(start + len, 0.0);
+        self.synth.render(voice, &mut self.samples[start..]);
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn write_wav(&self, writer: impl Write) -> io::Result<()> {
+        write_wav(writer, self.synth.sample_rate(), &self.samples)
+    }
+}
+
+// writes mono 16-bit pcm
+pub fn write_wav(mut writer: impl Write, sample_rate: u32, samples: &[f32]) -> io::Result<()> {
+    let data_len = (samples.len() * 2) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?; // chunk size
+    writer.write_all(&1u16.to_le_bytes())?; // pcm
+    writer.write_all(&1u16.to_le_bytes())?; // channels
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?; // byte rate
+    writer.write_all(&2u16.to_le_bytes())?; // block align
+    writer.write_all(&16u16.to_le_bytes())?; // bits per sample
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for &sample in samples {
+        let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_all(&pcm.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+
+    #[test]
+    fn test_playback_rate() {
+        let mut voice = Voice::default();
+        assert_eq!(voice.playback_rate(), 4000.0);
+
+        voice.pitch = 64 + 48;
+        assert_eq!(voice.playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn test_silent_without_sound_timer() {
+        let mut synth = Synth::new(8000);
+        let mut out = [1.0; 32];
+
+        synth.render(&Voice::default(), &mut out);
+
+        assert!(out.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_pattern_playback() {
+        // at 4000 bits per second and 8000 samples per second, every bit of
+        // the pattern lasts exactly two samples
+        let mut synth = Synth::new(8000);
+        let mut pattern = [0x00; 16];
+        pattern[0] = 0xA0;
+        let voice = Voice {
+            pattern,
+            pitch: 64,
+            playing: true,
+            sample: None,
+        };
+        let mut out = [0.0; 8];
+
+        synth.render(&voice, &mut out);
+
+        let high = VOLUME;
+        let low = -VOLUME;
+        assert_eq!(out, [high, high, low, low, high, high, low, low]);
+    }
+
+    #[test]
+    fn test_sample_playback() {
+        let mut synth = Synth::new(8000);
+        let mut voice = Voice {
+            sample: Some(SampleVoice {
+                id: 1,
+                rate: 4000,
+                looping: false,
+                data: Arc::from([0x80, 0xC0, 0x40]),
+            }),
+            ..Voice::default()
+        };
+        let mut out = [1.0; 8];
+
+        synth.render(&voice, &mut out);
+
+        let half = VOLUME / 2.0;
+        assert_eq!(out, [0.0, 0.0, half, half, -half, -half, 0.0, 0.0]);
+
+        // the same sample carries on where it was, looping from the start
+        voice.sample.as_mut().unwrap().looping = true;
+        synth.render(&voice, &mut out);
+        assert_eq!(out, [0.0, 0.0, half, half, -half, -half, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_rom_to_wav() {
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        // LD I, 0x300 ; AUDIO ; LD V0, 112 ; PITCH V0 ; LD V1, 2 ; LD ST, V1 ; JP 0x20C
+        let program = [
+            0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A, 0x61, 0x02, 0xF1, 0x18, 0x12, 0x0C,
+        ];
+        cpu.ram[0x200..0x20E].copy_from_slice(&program);
+        cpu.ram[0x300..0x310].copy_from_slice(&[0xFF; 16]);
+        cpu.st = 0;
+
+        let mut renderer = OfflineRenderer::new(48000);
+        for _ in 0..4 {
+            cpu.run_frame(10).unwrap();
+            renderer.record_frame(&cpu.voice());
+        }
+
+        assert_eq!(cpu.pattern, [0xFF; 16]);
+        assert_eq!(cpu.pitch, 112);
+
+        // the sound timer was 2: one frame of sound after the tick, then silence
+        let frame = 48000 / 60;
+        let samples = renderer.samples();
+        assert_eq!(samples.len(), 4 * frame);
+        assert!(samples[0..frame].iter().all(|&sample| sample == VOLUME));
+        assert!(samples[frame..].iter().all(|&sample| sample == 0.0));
+
+        let mut wav = Vec::new();
+        renderer.write_wav(&mut wav).unwrap();
+
+        assert_eq!(wav.len(), 44 + samples.len() * 2);
+        assert_eq!(wav[0..4], *b"RIFF");
+        assert_eq!(wav[8..12], *b"WAVE");
+        assert_eq!(wav[24..28], 48000u32.to_le_bytes());
+        assert_eq!(
+            wav[44..46],
+            ((VOLUME * i16::MAX as f32) as i16).to_le_bytes()
+        );
+    }
+}
@@ -0,0 +1,55 @@
+use chip8::disasm::Disassembly;
+use chip8::platform::Platform;
+use chip8::syntax::Syntax;
+
+use std::env;
+use std::error::Error;
+use std::fs;
+
+const USAGE: &str = concat!(
+    "usage: chip8-dis [--platform vip|hires|chip8x|chip48|schip|xochip|megachip|modern]",
+    " [--syntax cowgod|octo|english] ROM"
+);
+
+struct Args {
+    filename: String,
+    platform: Platform,
+    syntax: Option<Syntax>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut filename = None;
+    let mut platform = Platform::default();
+    let mut syntax = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().ok_or(USAGE)?.parse()?,
+            "--syntax" => syntax = Some(args.next().ok_or(USAGE)?.parse()?),
+            _ if filename.is_none() => filename = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok(Args {
+        filename: filename.ok_or(USAGE)?,
+        platform,
+        syntax,
+    })
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args()?;
+    let rom = fs::read(&args.filename)?;
+    let dis = Disassembly::new(&rom, args.platform)?;
+
+    // Octo source by default, it assembles back to the same rom. any other
+    // syntax gets an address-per-line listing
+    match args.syntax {
+        None | Some(Syntax::Octo) => print!("{}", dis.source()),
+        Some(syntax) => print!("{}", dis.listing(syntax)),
+    }
+
+    Ok(())
+}
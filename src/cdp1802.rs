@@ -0,0 +1,471 @@
+use crate::{Error, Result};
+
+// the RCA CDP1802 the COSMAC VIP is built around, enough of it to run the
+// machine code subroutines hybrid roms call through 0NNN. there's no dma
+// or interrupt hardware behind it, so IDL does nothing and the EF inputs
+// and the input bus are whatever the caller leaves in `ef` and `bus`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Cdp1802 {
+    pub r: [u16; 16],  // scratchpad registers
+    pub d: u8,         // accumulator
+    pub df: bool,      // carry / not borrow
+    pub p: u8,         // which r is the program counter
+    pub x: u8,         // which r addresses memory for the X ops
+    pub t: u8,         // x and p saved by MARK and interrupts
+    pub ie: bool,      // interrupt enable
+    pub q: bool,       // the Q output, the VIP's speaker
+    pub ef: [bool; 4], // EF1-EF4 inputs
+    pub bus: u8,       // what INP reads
+}
+
+impl Cdp1802 {
+    pub fn new() -> Cdp1802 {
+        Cdp1802 {
+            ie: true,
+            ..Default::default()
+        }
+    }
+
+    // executes one instruction against `ram`, which addresses wrap around,
+    // and returns how many machine cycles it took
+    pub fn step(&mut self, ram: &mut [u8]) -> Result<u32> {
+        let addr = self.r[self.p as usize];
+        let opcode = self.fetch(ram);
+        let n = (opcode & 0xF) as usize;
+        let x = self.x as usize;
+
+        match opcode >> 4 {
+            0x0 if n == 0 => {}                           // IDL
+            0x0 => self.d = read(ram, self.r[n]),         // LDN
+            0x1 => self.r[n] = self.r[n].wrapping_add(1), // INC
+            0x2 => self.r[n] = self.r[n].wrapping_sub(1), // DEC
+            0x3 => {
+                // short branches stay in the page of their operand byte
+                let page = self.r[self.p as usize] & 0xFF00;
+                let target = self.fetch(ram);
+                if self.condition(n) {
+                    self.r[self.p as usize] = page | target as u16;
+                }
+            }
+            0x4 => {
+                // LDA
+                self.d = read(ram, self.r[n]);
+                self.r[n] = self.r[n].wrapping_add(1);
+            }
+            0x5 => write(ram, self.r[n], self.d), // STR
+            0x6 => match n {
+                0x0 => self.r[x] = self.r[x].wrapping_add(1), // IRX
+                0x8 => return Err(Error::IllegalMachineCode { addr, opcode }),
+                0x1..=0x7 => {
+                    // OUT, nothing is listening
+                    self.r[x] = self.r[x].wrapping_add(1);
+                }
+                _ => {
+                    // INP
+                    write(ram, self.r[x], self.bus);
+                    self.d = self.bus;
+                }
+            },
+            0x7 => self.on_7n(ram, n),
+            0x8 => self.d = self.r[n] as u8,        // GLO
+            0x9 => self.d = (self.r[n] >> 8) as u8, // GHI
+            0xA => self.r[n] = (self.r[n] & 0xFF00) | self.d as u16, // PLO
+            0xB => self.r[n] = (self.r[n] & 0x00FF) | ((self.d as u16) << 8), // PHI
+            0xC => {
+                self.on_long(ram, n);
+                return Ok(3);
+            }
+            0xD => self.p = n as u8, // SEP
+            0xE => self.x = n as u8, // SEX
+            _ => self.on_fn(ram, n),
+        }
+
+        Ok(2)
+    }
+
+    fn fetch(&mut self, ram: &[u8]) -> u8 {
+        let pc = &mut self.r[self.p as usize];
+        let byte = read(ram, *pc);
+        *pc = pc.wrapping_add(1);
+        byte
+    }
+
+    // the branch conditions for 3N and CN. the low three bits pick the
+    // test, bit 3 inverts it
+    fn condition(&self, n: usize) -> bool {
+        let test = match n & 0x7 {
+            0x0 => true,
+            0x1 => self.q,
+            0x2 => self.d == 0,
+            0x3 => self.df,
+            k => self.ef[k - 4],
+        };
+
+        test != (n & 0x8 != 0)
+    }
+
+    fn on_7n(&mut self, ram: &mut [u8], n: usize) {
+        let x = self.x as usize;
+        match n {
+            0x0 | 0x1 => {
+                // RET / DIS
+                let xp = read(ram, self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+                self.x = xp >> 4;
+                self.p = xp & 0xF;
+                self.ie = n == 0x0;
+            }
+            0x2 => {
+                // LDXA
+                self.d = read(ram, self.r[x]);
+                self.r[x] = self.r[x].wrapping_add(1);
+            }
+            0x3 => {
+                // STXD
+                write(ram, self.r[x], self.d);
+                self.r[x] = self.r[x].wrapping_sub(1);
+            }
+            0x4 => self.add(read(ram, self.r[x]), self.df), // ADC
+            0x5 => self.subtract(read(ram, self.r[x]), self.d, self.df), // SDB
+            0x6 => {
+                // SHRC
+                let carry = self.d & 0x1 != 0;
+                self.d = (self.d >> 1) | ((self.df as u8) << 7);
+                self.df = carry;
+            }
+            0x7 => self.subtract(self.d, read(ram, self.r[x]), self.df), // SMB
+            0x8 => write(ram, self.r[x], self.t),                        // SAV
+            0x9 => {
+                // MARK
+                self.t = (self.x << 4) | self.p;
+                write(ram, self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            0xA => self.q = false, // REQ
+            0xB => self.q = true,  // SEQ
+            0xC => {
+                // ADCI
+                let imm = self.fetch(ram);
+                self.add(imm, self.df);
+            }
+            0xD => {
+                // SDBI
+                let imm = self.fetch(ram);
+                self.subtract(imm, self.d, self.df);
+            }
+            0xE => {
+                // SHLC
+                let carry = self.d & 0x80 != 0;
+                self.d = (self.d << 1) | self.df as u8;
+                self.df = carry;
+            }
+            _ => {
+                // SMBI
+                let imm = self.fetch(ram);
+                self.subtract(self.d, imm, self.df);
+            }
+        }
+    }
+
+    // long branches jump to the next two bytes, long skips step over them
+    fn on_long(&mut self, ram: &[u8], n: usize) {
+        let skip = matches!(n, 0x4..=0x8 | 0xC..=0xF);
+        let taken = match n {
+            0x4 => false,       // NOP
+            0x5 => !self.q,     // LSNQ
+            0x6 => self.d != 0, // LSNZ
+            0x7 => !self.df,    // LSNF
+            0x8 => true,        // LSKP
+            0xC => self.ie,     // LSIE
+            0xD => self.q,      // LSQ
+            0xE => self.d == 0, // LSZ
+            0xF => self.df,     // LSDF
+            _ => self.condition(n),
+        };
+
+        let pc = self.r[self.p as usize];
+        self.r[self.p as usize] = match (taken, skip) {
+            (true, false) => u16::from_be_bytes([read(ram, pc), read(ram, pc.wrapping_add(1))]),
+            (false, false) | (true, true) => pc.wrapping_add(2),
+            (false, true) => pc,
+        };
+    }
+
+    fn on_fn(&mut self, ram: &mut [u8], n: usize) {
+        // F8-FF take an immediate byte instead of M(R(X)), except SHL
+        let operand = match n {
+            0x6 | 0xE => 0,
+            0x8..=0xF => self.fetch(ram),
+            _ => read(ram, self.r[self.x as usize]),
+        };
+
+        match n {
+            0x0 | 0x8 => self.d = operand,                     // LDX / LDI
+            0x1 | 0x9 => self.d |= operand,                    // OR / ORI
+            0x2 | 0xA => self.d &= operand,                    // AND / ANI
+            0x3 | 0xB => self.d ^= operand,                    // XOR / XRI
+            0x4 | 0xC => self.add(operand, false),             // ADD / ADI
+            0x5 | 0xD => self.subtract(operand, self.d, true), // SD / SDI
+            0x6 => {
+                // SHR
+                self.df = self.d & 0x1 != 0;
+                self.d >>= 1;
+            }
+            0x7 | 0xF => self.subtract(self.d, operand, true), // SM / SMI
+            _ => {
+                // SHL
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+        }
+    }
+
+    fn add(&mut self, operand: u8, carry: bool) {
+        let sum = self.d as u16 + operand as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // D = a - b, with DF set when there was no borrow
+    fn subtract(&mut self, a: u8, b: u8, no_borrow: bool) {
+        let diff = a as i16 - b as i16 - !no_borrow as i16;
+        self.d = diff as u8;
+        self.df = diff >= 0;
+    }
+}
+
+fn read(ram: &[u8], addr: u16) -> u8 {
+    ram[addr as usize % ram.len()]
+}
+
+fn write(ram: &mut [u8], addr: u16, value: u8) {
+    let len = ram.len();
+    ram[addr as usize % len] = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // loads `program` at 0 and runs `steps` instructions of it with R0 as
+    // the program counter
+    fn run(program: &[u8], steps: usize) -> (Cdp1802, [u8; 0x100]) {
+        let mut ram = [0; 0x100];
+        ram[..program.len()].copy_from_slice(program);
+        let mut cdp = Cdp1802::new();
+        for _ in 0..steps {
+            cdp.step(&mut ram).unwrap();
+        }
+        (cdp, ram)
+    }
+
+    #[test]
+    fn test_add_with_carry() {
+        // LDI 0x80 ; PLO R5 ; SEX 5 ; LDI 0xF0 ; ADD ; ADC ; ADI 0x0E
+        let mut program = [0; 0x81];
+        program[..10]
+            .copy_from_slice(&[0xF8, 0x80, 0xA5, 0xE5, 0xF8, 0xF0, 0xF4, 0x74, 0xFC, 0x0E]);
+        program[0x80] = 0x20;
+
+        let (cdp, _) = run(&program, 5);
+        assert_eq!((cdp.d, cdp.df), (0x10, true));
+
+        let (cdp, _) = run(&program, 6);
+        assert_eq!((cdp.d, cdp.df), (0x31, false));
+
+        let (cdp, _) = run(&program, 7);
+        assert_eq!((cdp.d, cdp.df), (0x3F, false));
+    }
+
+    #[test]
+    fn test_subtract_with_borrow() {
+        // LDI 0x10 ; SMI 0x20 ; SMBI 0x00 ; SDI 0x05 ; SDBI 0x05
+        let program = [0xF8, 0x10, 0xFF, 0x20, 0x7F, 0x00, 0xFD, 0x05, 0x7D, 0x05];
+
+        let (cdp, _) = run(&program, 2);
+        assert_eq!((cdp.d, cdp.df), (0xF0, false));
+
+        let (cdp, _) = run(&program, 3);
+        assert_eq!((cdp.d, cdp.df), (0xEF, true));
+
+        let (cdp, _) = run(&program, 4);
+        assert_eq!((cdp.d, cdp.df), (0x16, false));
+
+        let (cdp, _) = run(&program, 5);
+        assert_eq!((cdp.d, cdp.df), (0xEE, false));
+    }
+
+    #[test]
+    fn test_shifts() {
+        // LDI 0x81 ; SHR ; SHLC ; SHRC ; SHL
+        let program = [0xF8, 0x81, 0xF6, 0x7E, 0x76, 0xFE];
+
+        let (cdp, _) = run(&program, 2);
+        assert_eq!((cdp.d, cdp.df), (0x40, true));
+
+        let (cdp, _) = run(&program, 3);
+        assert_eq!((cdp.d, cdp.df), (0x81, false));
+
+        let (cdp, _) = run(&program, 4);
+        assert_eq!((cdp.d, cdp.df), (0x40, true));
+
+        let (cdp, _) = run(&program, 5);
+        assert_eq!((cdp.d, cdp.df), (0x80, false));
+    }
+
+    #[test]
+    fn test_logic() {
+        // LDI 0xF0 ; ORI 0x0C ; ANI 0x3C ; XRI 0xFF ; SEX 0 ; LDX
+        let (cdp, _) = run(&[0xF8, 0xF0, 0xF9, 0x0C, 0xFA, 0x3C, 0xFB, 0xFF], 4);
+        assert_eq!(cdp.d, 0xC3);
+
+        let (cdp, _) = run(&[0xE0, 0xF0, 0xAB], 2);
+        assert_eq!(cdp.d, 0xAB);
+    }
+
+    #[test]
+    fn test_register_ops() {
+        // LDI 0x80 ; PLO R7 ; LDI 0x00 ; PHI R7 ; INC R7 ; DEC R7 ; DEC R7 ;
+        // GLO R7 ; STR R7 ; LDA R7 ; GHI R7 ; LDN R7
+        let program = [
+            0xF8, 0x80, 0xA7, 0xF8, 0x00, 0xB7, 0x17, 0x27, 0x27, 0x87, 0x57, 0x47, 0x97, 0x07,
+        ];
+
+        let (cdp, ram) = run(&program, 8);
+        assert_eq!(cdp.r[7], 0x7F);
+        assert_eq!(cdp.d, 0x7F);
+
+        let (cdp, ram2) = run(&program, 10);
+        assert_eq!(ram[0x7F], 0x00);
+        assert_eq!(ram2[0x7F], 0x7F);
+        assert_eq!((cdp.d, cdp.r[7]), (0x7F, 0x80));
+
+        let (cdp, _) = run(&program, 12);
+        assert_eq!(cdp.d, 0x00);
+    }
+
+    #[test]
+    fn test_short_branches() {
+        // LDI 0 ; BZ 0x10 / at 0x10: BNZ 0x00 ; BR 0x20
+        let mut program = [0; 0x12];
+        program[..4].copy_from_slice(&[0xF8, 0x00, 0x32, 0x10]);
+        program[0x10..0x12].copy_from_slice(&[0x3A, 0x00]);
+
+        let (cdp, _) = run(&program, 2);
+        assert_eq!(cdp.r[0], 0x10);
+
+        let (cdp, _) = run(&program, 3);
+        assert_eq!(cdp.r[0], 0x12);
+
+        // the branch stays within the page of its operand
+        let mut ram = [0; 0x300];
+        ram[0x1FE..0x200].copy_from_slice(&[0x30, 0x40]);
+        let mut cdp = Cdp1802::new();
+        cdp.r[0] = 0x1FE;
+        cdp.step(&mut ram).unwrap();
+        assert_eq!(cdp.r[0], 0x140);
+    }
+
+    #[test]
+    fn test_long_branches_and_skips() {
+        // SEQ ; LBQ 0x0040
+        let (cdp, _) = run(&[0x7B, 0xC1, 0x00, 0x40], 2);
+        assert_eq!(cdp.r[0], 0x40);
+
+        // SEQ ; LBNQ 0x0040 isn't taken, and steps over its address
+        let (cdp, _) = run(&[0x7B, 0xC9, 0x00, 0x40], 2);
+        assert_eq!(cdp.r[0], 0x04);
+
+        // LDI 0 ; LSZ ; LSNZ ; NOP
+        let mut ram = [0; 0x10];
+        ram[..6].copy_from_slice(&[0xF8, 0x00, 0xCE, 0xFF, 0xFF, 0xC6]);
+        let mut cdp = Cdp1802::new();
+        cdp.step(&mut ram).unwrap();
+        assert_eq!(cdp.step(&mut ram).unwrap(), 3);
+        assert_eq!(cdp.r[0], 0x05);
+        cdp.step(&mut ram).unwrap();
+        assert_eq!(cdp.r[0], 0x06);
+
+        let (cdp, _) = run(&[0xC4], 1);
+        assert_eq!(cdp.r[0], 0x01);
+    }
+
+    #[test]
+    fn test_mark_and_ret() {
+        // LDI 0x80 ; PLO R2 ; SEX 5 ; MARK ; SEX 2 ; INC R2 ; DIS
+        let mut cdp = Cdp1802::new();
+        let mut ram = [0; 0x100];
+        ram[..9].copy_from_slice(&[0xF8, 0x80, 0xA2, 0xE5, 0x79, 0xE2, 0x12, 0x71, 0x00]);
+        for _ in 0..4 {
+            cdp.step(&mut ram).unwrap();
+        }
+        assert_eq!(cdp.t, 0x50);
+        assert_eq!(ram[0x80], 0x50);
+        assert_eq!((cdp.x, cdp.r[2]), (0, 0x7F));
+
+        for _ in 0..3 {
+            cdp.step(&mut ram).unwrap();
+        }
+        assert_eq!((cdp.x, cdp.p), (5, 0));
+        assert_eq!(cdp.r[2], 0x81);
+        assert!(!cdp.ie);
+    }
+
+    #[test]
+    fn test_stxd_ldxa() {
+        // LDI 0x90 ; PLO R2 ; SEX 2 ; LDI 0xAB ; STXD ; IRX ; LDI 0 ; LDXA
+        let program = [
+            0xF8, 0x90, 0xA2, 0xE2, 0xF8, 0xAB, 0x73, 0x60, 0xF8, 0x00, 0x72,
+        ];
+
+        let (cdp, ram) = run(&program, 5);
+        assert_eq!(ram[0x90], 0xAB);
+        assert_eq!(cdp.r[2], 0x8F);
+
+        let (cdp, _) = run(&program, 8);
+        assert_eq!(cdp.d, 0xAB);
+        assert_eq!(cdp.r[2], 0x91);
+    }
+
+    #[test]
+    fn test_sep_and_io() {
+        // LDI 0x10 ; PLO R3 ; SEP R3 / at 0x10: LDI 0x80 ; PLO R5 ; SEX 5 ;
+        // INP 1 ; OUT 1
+        let mut program = [0; 0x17];
+        program[..4].copy_from_slice(&[0xF8, 0x10, 0xA3, 0xD3]);
+        program[0x10..0x17].copy_from_slice(&[0xF8, 0x80, 0xA5, 0xE5, 0x69, 0x61, 0x00]);
+        let mut ram = [0; 0x100];
+        ram[..program.len()].copy_from_slice(&program);
+        let mut cdp = Cdp1802::new();
+        cdp.bus = 0x5A;
+
+        for _ in 0..4 {
+            cdp.step(&mut ram).unwrap();
+        }
+        assert_eq!((cdp.p, cdp.r[3]), (3, 0x12));
+
+        for _ in 0..4 {
+            cdp.step(&mut ram).unwrap();
+        }
+        assert_eq!(cdp.d, 0x5A);
+        // INP stored the bus at R5 and OUT stepped past it
+        assert_eq!(ram[0x80], 0x5A);
+        assert_eq!(cdp.r[5], 0x81);
+        assert_eq!(cdp.r[3], 0x16);
+    }
+
+    #[test]
+    fn test_illegal_opcode() {
+        let mut ram = [0x68; 0x10];
+        let mut cdp = Cdp1802::new();
+
+        assert!(matches!(
+            cdp.step(&mut ram),
+            Err(Error::IllegalMachineCode {
+                addr: 0x0,
+                opcode: 0x68
+            })
+        ));
+    }
+}
@@ -1,19 +1,40 @@
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::BufReader;
+#[cfg(feature = "std")]
 use std::io::Read;
-
-use rand::Rng;
-
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::cdp1802::Cdp1802;
+use crate::font::{BigFont, SmallFont};
+use crate::megachip::{self, BlendMode, MegaChip};
+#[cfg(not(feature = "std"))]
+use crate::platform::MAX_STACK_DEPTH;
+use crate::platform::{MAX_HEIGHT, MAX_MEMORY, MAX_WIDTH, Platform};
+use crate::quirks::Quirks;
+use crate::policy::{MemoryPolicy, OpcodePolicy};
+use crate::random::{RandomSource, XorShift};
+use crate::syntax::Syntax;
+use crate::timing::VipTiming;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JPType {
     Addr(u16),
     FromV0(u16),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SEType {
     Byte(u8),
     Reg(u8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LDType {
     Byte(u8),
     Reg(u8),
@@ -26,14 +47,22 @@ pub enum LDType {
     B,
     ToI,
     FromI,
+    HF,
+    ToR,
+    FromR,
+    LongAddr,
+    ToIRange(u8),
+    FromIRange(u8),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AddType {
     Byte(u8),
     Reg(u8),
     I,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instruction {
     CLS,
     RET,
@@ -55,54 +84,122 @@ pub enum Instruction {
     DRW(u8, u8, u8),
     SKP(u8),
     SKNP(u8),
+    SCD(u8),
+    SCU(u8),
+    SCR,
+    SCL,
+    EXIT,
+    LOW,
+    HIGH,
+    PLANE(u8),
+    AUDIO,
+    PITCH(u8),
+    BGC,
+    COL(u8, u8, u8),
+    SKP2(u8),
+    SKNP2(u8),
+    MEGAOFF,
+    MEGAON,
+    LDHI(u8),
+    LDPAL(u8),
+    SPRW(u8),
+    SPRH(u8),
+    ALPHA(u8),
+    DIGISND(u8),
+    STOPSND,
+    BMODE(u8),
+    CCOL(u8),
     RAW0,
 }
 
-impl ToString for Instruction {
-    fn to_string(&self) -> String {
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::RAW0 => "RAW0".to_string(),
-            Self::CLS => "CLS".to_string(),
-            Self::RET => "RET".to_string(),
-            Self::SYS(nnn) => format!("SYS {:#x}", nnn),
-            Self::JP(JPType::Addr(nnn)) => format!("JP {:#x}", nnn),
-            Self::JP(JPType::FromV0(nnn)) => format!("JP V0 + {:#x}", nnn),
-            Self::CALL(nnn) => format!("CALL {:#x}", nnn),
-            Self::SE(x, SEType::Byte(kk)) => format!("SE V{:#x}, {:#x}", x, kk),
-            Self::SE(x, SEType::Reg(y)) => format!("SE V{:#x}, V{:#x}", x, y),
-            Self::SNE(x, SEType::Byte(kk)) => format!("SNE V{:#x}, {:#x}", x, kk),
-            Self::SNE(x, SEType::Reg(y)) => format!("SNE V{:#x}, V{:#x}", x, y),
-            Self::LD(_, LDType::Addr(nnn)) => format!("LD I, {:#x}", nnn),
-            Self::LD(x, LDType::B) => format!("LD B, V{:#x}", x),
-            Self::LD(x, LDType::Byte(kk)) => format!("LD V{:#x}, {:#x}", x, kk),
-            Self::LD(x, LDType::F) => format!("LD F, V{:#x}", x),
-            Self::LD(x, LDType::FromDT) => format!("LD V{:#x}, DT", x),
-            Self::LD(x, LDType::FromI) => format!("LD V{:#x}, [I]", x),
-            Self::LD(x, LDType::KeyPress) => format!("LD V{:#x}, K", x),
-            Self::LD(x, LDType::Reg(y)) => format!("LD V{:#x}, V{:#x}", x, y),
-            Self::LD(x, LDType::ToDT) => format!("LD DT, V{:#x}", x),
-            Self::LD(x, LDType::ToI) => format!("LD [I], V{:#x}", x),
-            Self::LD(x, LDType::ToST) => format!("LD ST, V{:#x}", x),
-            Self::ADD(x, AddType::Byte(kk)) => format!("ADD V{:#x}, {:#x}", x, kk),
-            Self::ADD(x, AddType::I) => format!("ADD V{:#x}, I", x),
-            Self::ADD(x, AddType::Reg(y)) => format!("ADD V{:#x}, V{:#x}", x, y),
-            Self::OR(x, y) => format!("OR V{:#x}, V{:#x}", x, y),
-            Self::AND(x, y) => format!("AND V{:#x}, V{:#x}", x, y),
-            Self::XOR(x, y) => format!("XOR V{:#x}, V{:#x}", x, y),
-            Self::SUB(x, y) => format!("SUB V{:#x}, V{:#x}", x, y),
-            Self::SHR(x, y) => format!("SHR V{:#x} {{, V{:#x}}}", x, y),
-            Self::SUBN(x, y) => format!("SUBN V{:#x}, V{:#x}", x, y),
-            Self::SHL(x, y) => format!("SHL V{:#x} {{, V{:#x}}}", x, y),
-            Self::RND(x, kk) => format!("RND V{:#x}, {:#x}", x, kk),
-            Self::DRW(x, y, n) => format!("DRW V{:#x}, V{:#x}, {:#x}", x, y, n),
-            Self::SKP(x) => format!("SKP V{:#x}", x),
-            Self::SKNP(x) => format!("SKNP V{:#x}", x),
+            Self::RAW0 => write!(f, "RAW0"),
+            Self::CLS => write!(f, "CLS"),
+            Self::RET => write!(f, "RET"),
+            Self::SYS(nnn) => write!(f, "SYS {:#x}", nnn),
+            Self::JP(JPType::Addr(nnn)) => write!(f, "JP {:#x}", nnn),
+            Self::JP(JPType::FromV0(nnn)) => write!(f, "JP V0, {:#x}", nnn),
+            Self::CALL(nnn) => write!(f, "CALL {:#x}", nnn),
+            Self::SE(x, SEType::Byte(kk)) => write!(f, "SE V{:X}, {:#x}", x, kk),
+            Self::SE(x, SEType::Reg(y)) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::SNE(x, SEType::Byte(kk)) => write!(f, "SNE V{:X}, {:#x}", x, kk),
+            Self::SNE(x, SEType::Reg(y)) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::LD(_, LDType::Addr(nnn)) => write!(f, "LD I, {:#x}", nnn),
+            Self::LD(x, LDType::B) => write!(f, "LD B, V{:X}", x),
+            Self::LD(x, LDType::Byte(kk)) => write!(f, "LD V{:X}, {:#x}", x, kk),
+            Self::LD(x, LDType::F) => write!(f, "LD F, V{:X}", x),
+            Self::LD(x, LDType::FromDT) => write!(f, "LD V{:X}, DT", x),
+            Self::LD(x, LDType::FromI) => write!(f, "LD V{:X}, [I]", x),
+            Self::LD(x, LDType::KeyPress) => write!(f, "LD V{:X}, K", x),
+            Self::LD(x, LDType::Reg(y)) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::LD(x, LDType::ToDT) => write!(f, "LD DT, V{:X}", x),
+            Self::LD(x, LDType::ToI) => write!(f, "LD [I], V{:X}", x),
+            Self::LD(x, LDType::ToST) => write!(f, "LD ST, V{:X}", x),
+            Self::ADD(x, AddType::Byte(kk)) => write!(f, "ADD V{:X}, {:#x}", x, kk),
+            Self::ADD(x, AddType::I) => write!(f, "ADD I, V{:X}", x),
+            Self::ADD(x, AddType::Reg(y)) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::OR(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::AND(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::XOR(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::SUB(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::SHR(x, y) => write!(f, "SHR V{:X} {{, V{:X}}}", x, y),
+            Self::SUBN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::SHL(x, y) => write!(f, "SHL V{:X} {{, V{:X}}}", x, y),
+            Self::RND(x, kk) => write!(f, "RND V{:X}, {:#x}", x, kk),
+            Self::DRW(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {:#x}", x, y, n),
+            Self::SKP(x) => write!(f, "SKP V{:X}", x),
+            Self::SKNP(x) => write!(f, "SKNP V{:X}", x),
+            Self::SCD(n) => write!(f, "SCD {:#x}", n),
+            Self::SCU(n) => write!(f, "SCU {:#x}", n),
+            Self::PLANE(n) => write!(f, "PLANE {:#x}", n),
+            Self::AUDIO => write!(f, "AUDIO"),
+            Self::PITCH(x) => write!(f, "PITCH V{:X}", x),
+            Self::BGC => write!(f, "BGC"),
+            Self::COL(x, y, n) => write!(f, "COL V{:X}, V{:X}, {:#x}", x, y, n),
+            Self::SKP2(x) => write!(f, "SKP2 V{:X}", x),
+            Self::SKNP2(x) => write!(f, "SKNP2 V{:X}", x),
+            Self::MEGAOFF => write!(f, "MEGAOFF"),
+            Self::MEGAON => write!(f, "MEGAON"),
+            Self::LDHI(nn) => write!(f, "LDHI I, {:#x}, LONG", nn),
+            Self::LDPAL(nn) => write!(f, "LDPAL {:#x}", nn),
+            Self::SPRW(nn) => write!(f, "SPRW {:#x}", nn),
+            Self::SPRH(nn) => write!(f, "SPRH {:#x}", nn),
+            Self::ALPHA(nn) => write!(f, "ALPHA {:#x}", nn),
+            Self::DIGISND(n) => write!(f, "DIGISND {:#x}", n),
+            Self::STOPSND => write!(f, "STOPSND"),
+            Self::BMODE(n) => write!(f, "BMODE {:#x}", n),
+            Self::CCOL(nn) => write!(f, "CCOL {:#x}", nn),
+            Self::LD(_, LDType::LongAddr) => write!(f, "LD I, LONG"),
+            Self::LD(x, LDType::ToIRange(y)) => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            Self::LD(x, LDType::FromIRange(y)) => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            Self::SCR => write!(f, "SCR"),
+            Self::SCL => write!(f, "SCL"),
+            Self::EXIT => write!(f, "EXIT"),
+            Self::LOW => write!(f, "LOW"),
+            Self::HIGH => write!(f, "HIGH"),
+            Self::LD(x, LDType::HF) => write!(f, "LD HF, V{:X}", x),
+            Self::LD(x, LDType::ToR) => write!(f, "LD R, V{:X}", x),
+            Self::LD(x, LDType::FromR) => write!(f, "LD V{:X}, R", x),
         }
     }
 }
 
 impl Instruction {
+    // decodes the original CHIP-8 instruction set
     pub fn decode(instruction: u16) -> Option<Self> {
+        Self::decode_for(instruction, Platform::Modern)
+    }
+
+    // decodes including the extensions `platform` supports
+    pub fn decode_for(instruction: u16, platform: Platform) -> Option<Self> {
+        let superchip = platform.supports_superchip();
+        let xochip = platform.supports_xochip();
+        let chip8x = platform.supports_chip8x();
+        let megachip = platform.supports_megachip();
+        let two_page = platform.supports_two_page();
+
         let nibbles = (
             (0xF000 & instruction) >> 12,
             (0x0F00 & instruction) >> 8,
@@ -120,12 +217,35 @@ impl Instruction {
             (0x0, 0x0, 0x0, 0x0) => Instruction::RAW0,
             (0x0, 0x0, 0xE, 0x0) => Instruction::CLS,
             (0x0, 0x0, 0xE, 0xE) => Instruction::RET,
+            (0x0, 0x0, 0xC, _) if superchip => Instruction::SCD(n),
+            (0x0, 0x0, 0xD, _) if xochip => Instruction::SCU(n),
+            (0x0, 0x0, 0xB, _) if megachip => Instruction::SCU(n),
+            (0x0, 0x0, 0x1, 0x0) if megachip => Instruction::MEGAOFF,
+            (0x0, 0x0, 0x1, 0x1) if megachip => Instruction::MEGAON,
+            (0x0, 0x1, _, _) if megachip => Instruction::LDHI(kk),
+            (0x0, 0x2, _, _) if megachip => Instruction::LDPAL(kk),
+            (0x0, 0x3, _, _) if megachip => Instruction::SPRW(kk),
+            (0x0, 0x4, _, _) if megachip => Instruction::SPRH(kk),
+            (0x0, 0x5, _, _) if megachip => Instruction::ALPHA(kk),
+            (0x0, 0x6, 0x0, _) if megachip => Instruction::DIGISND(n),
+            (0x0, 0x7, 0x0, 0x0) if megachip => Instruction::STOPSND,
+            (0x0, 0x8, 0x0, _) if megachip => Instruction::BMODE(n),
+            (0x0, 0x9, _, _) if megachip => Instruction::CCOL(kk),
+            (0x0, 0x0, 0xF, 0xB) if superchip => Instruction::SCR,
+            (0x0, 0x0, 0xF, 0xC) if superchip => Instruction::SCL,
+            (0x0, 0x0, 0xF, 0xD) if superchip => Instruction::EXIT,
+            (0x0, 0x0, 0xF, 0xE) if superchip => Instruction::LOW,
+            (0x0, 0x0, 0xF, 0xF) if superchip => Instruction::HIGH,
+            (0x0, 0x2, 0xA, 0x0) if chip8x => Instruction::BGC,
+            (0x0, 0x2, 0x3, 0x0) if two_page => Instruction::CLS,
             (0x0, _, _, _) => Instruction::SYS(nnn),
             (0x1, _, _, _) => Instruction::JP(JPType::Addr(nnn)),
             (0x2, _, _, _) => Instruction::CALL(nnn),
             (0x3, _, _, _) => Instruction::SE(x, SEType::Byte(kk)),
             (0x4, _, _, _) => Instruction::SNE(x, SEType::Byte(kk)),
             (0x5, _, _, 0x0) => Instruction::SE(x, SEType::Reg(y)),
+            (0x5, _, _, 0x2) if xochip => Instruction::LD(x, LDType::ToIRange(y)),
+            (0x5, _, _, 0x3) if xochip => Instruction::LD(x, LDType::FromIRange(y)),
             (0x6, _, _, _) => Instruction::LD(x, LDType::Byte(kk)),
             (0x7, _, _, _) => Instruction::ADD(x, AddType::Byte(kk)),
             (0x8, _, _, 0x0) => Instruction::LD(x, LDType::Reg(y)),
@@ -139,131 +259,703 @@ impl Instruction {
             (0x8, _, _, 0xE) => Instruction::SHL(x, y),
             (0x9, _, _, 0x0) => Instruction::SNE(x, SEType::Reg(y)),
             (0xA, _, _, _) => Instruction::LD(0, LDType::Addr(nnn)),
+            (0xB, _, _, _) if chip8x => Instruction::COL(x, y, n),
             (0xB, _, _, _) => Instruction::JP(JPType::FromV0(nnn)),
             (0xC, _, _, _) => Instruction::RND(x, kk),
             (0xD, _, _, _) => Instruction::DRW(x, y, n),
             (0xE, _, 0x9, 0xE) => Instruction::SKP(x),
             (0xE, _, 0xA, 0x1) => Instruction::SKNP(x),
+            (0xE, _, 0xF, 0x2) if chip8x => Instruction::SKP2(x),
+            (0xE, _, 0xF, 0x5) if chip8x => Instruction::SKNP2(x),
+            (0xF, 0x0, 0x0, 0x0) if xochip => Instruction::LD(0, LDType::LongAddr),
+            (0xF, _, 0x0, 0x1) if xochip => Instruction::PLANE(x),
+            (0xF, 0x0, 0x0, 0x2) if xochip => Instruction::AUDIO,
             (0xF, _, 0x0, 0x7) => Instruction::LD(x, LDType::FromDT),
             (0xF, _, 0x0, 0xA) => Instruction::LD(x, LDType::KeyPress),
             (0xF, _, 0x1, 0x5) => Instruction::LD(x, LDType::ToDT),
             (0xF, _, 0x1, 0x8) => Instruction::LD(x, LDType::ToST),
             (0xF, _, 0x1, 0xE) => Instruction::ADD(x, AddType::I),
             (0xF, _, 0x2, 0x9) => Instruction::LD(x, LDType::F),
+            (0xF, _, 0x3, 0x0) if superchip => Instruction::LD(x, LDType::HF),
             (0xF, _, 0x3, 0x3) => Instruction::LD(x, LDType::B),
+            (0xF, _, 0x3, 0xA) if xochip => Instruction::PITCH(x),
             (0xF, _, 0x5, 0x5) => Instruction::LD(x, LDType::ToI),
             (0xF, _, 0x6, 0x5) => Instruction::LD(x, LDType::FromI),
+            (0xF, _, 0x7, 0x5) if superchip => Instruction::LD(x, LDType::ToR),
+            (0xF, _, 0x8, 0x5) if superchip => Instruction::LD(x, LDType::FromR),
             _ => return None,
         })
     }
+
+    // the opcode this decodes from. SCU is XO-CHIP's 00DN, see encode_for.
+    // HiRes decodes 0230 as CLS too, that one encodes back to 00E0
+    pub fn encode(&self) -> u16 {
+        let xkk = |op: u16, x: u8, kk: u8| op | (x as u16) << 8 | kk as u16;
+        let xyn = |op: u16, x: u8, y: u8, n: u8| op | (x as u16) << 8 | (y as u16) << 4 | n as u16;
+
+        match *self {
+            Self::RAW0 => 0x0000,
+            Self::CLS => 0x00E0,
+            Self::RET => 0x00EE,
+            Self::SYS(nnn) => nnn,
+            Self::JP(JPType::Addr(nnn)) => 0x1000 | nnn,
+            Self::JP(JPType::FromV0(nnn)) => 0xB000 | nnn,
+            Self::CALL(nnn) => 0x2000 | nnn,
+            Self::SE(x, SEType::Byte(kk)) => xkk(0x3000, x, kk),
+            Self::SNE(x, SEType::Byte(kk)) => xkk(0x4000, x, kk),
+            Self::SE(x, SEType::Reg(y)) => xyn(0x5000, x, y, 0x0),
+            Self::SNE(x, SEType::Reg(y)) => xyn(0x9000, x, y, 0x0),
+            Self::LD(x, LDType::ToIRange(y)) => xyn(0x5000, x, y, 0x2),
+            Self::LD(x, LDType::FromIRange(y)) => xyn(0x5000, x, y, 0x3),
+            Self::LD(x, LDType::Byte(kk)) => xkk(0x6000, x, kk),
+            Self::ADD(x, AddType::Byte(kk)) => xkk(0x7000, x, kk),
+            Self::LD(x, LDType::Reg(y)) => xyn(0x8000, x, y, 0x0),
+            Self::OR(x, y) => xyn(0x8000, x, y, 0x1),
+            Self::AND(x, y) => xyn(0x8000, x, y, 0x2),
+            Self::XOR(x, y) => xyn(0x8000, x, y, 0x3),
+            Self::ADD(x, AddType::Reg(y)) => xyn(0x8000, x, y, 0x4),
+            Self::SUB(x, y) => xyn(0x8000, x, y, 0x5),
+            Self::SHR(x, y) => xyn(0x8000, x, y, 0x6),
+            Self::SUBN(x, y) => xyn(0x8000, x, y, 0x7),
+            Self::SHL(x, y) => xyn(0x8000, x, y, 0xE),
+            Self::LD(_, LDType::Addr(nnn)) => 0xA000 | nnn,
+            Self::COL(x, y, n) => xyn(0xB000, x, y, n),
+            Self::RND(x, kk) => xkk(0xC000, x, kk),
+            Self::DRW(x, y, n) => xyn(0xD000, x, y, n),
+            Self::SKP(x) => xkk(0xE000, x, 0x9E),
+            Self::SKNP(x) => xkk(0xE000, x, 0xA1),
+            Self::SKP2(x) => xkk(0xE000, x, 0xF2),
+            Self::SKNP2(x) => xkk(0xE000, x, 0xF5),
+            Self::LD(_, LDType::LongAddr) => 0xF000,
+            Self::PLANE(x) => xkk(0xF000, x, 0x01),
+            Self::AUDIO => 0xF002,
+            Self::LD(x, LDType::FromDT) => xkk(0xF000, x, 0x07),
+            Self::LD(x, LDType::KeyPress) => xkk(0xF000, x, 0x0A),
+            Self::LD(x, LDType::ToDT) => xkk(0xF000, x, 0x15),
+            Self::LD(x, LDType::ToST) => xkk(0xF000, x, 0x18),
+            Self::ADD(x, AddType::I) => xkk(0xF000, x, 0x1E),
+            Self::LD(x, LDType::F) => xkk(0xF000, x, 0x29),
+            Self::LD(x, LDType::HF) => xkk(0xF000, x, 0x30),
+            Self::LD(x, LDType::B) => xkk(0xF000, x, 0x33),
+            Self::PITCH(x) => xkk(0xF000, x, 0x3A),
+            Self::LD(x, LDType::ToI) => xkk(0xF000, x, 0x55),
+            Self::LD(x, LDType::FromI) => xkk(0xF000, x, 0x65),
+            Self::LD(x, LDType::ToR) => xkk(0xF000, x, 0x75),
+            Self::LD(x, LDType::FromR) => xkk(0xF000, x, 0x85),
+            Self::SCD(n) => 0x00C0 | n as u16,
+            Self::SCU(n) => 0x00D0 | n as u16,
+            Self::SCR => 0x00FB,
+            Self::SCL => 0x00FC,
+            Self::EXIT => 0x00FD,
+            Self::LOW => 0x00FE,
+            Self::HIGH => 0x00FF,
+            Self::BGC => 0x02A0,
+            Self::MEGAOFF => 0x0010,
+            Self::MEGAON => 0x0011,
+            Self::LDHI(nn) => 0x0100 | nn as u16,
+            Self::LDPAL(nn) => 0x0200 | nn as u16,
+            Self::SPRW(nn) => 0x0300 | nn as u16,
+            Self::SPRH(nn) => 0x0400 | nn as u16,
+            Self::ALPHA(nn) => 0x0500 | nn as u16,
+            Self::DIGISND(n) => 0x0600 | n as u16,
+            Self::STOPSND => 0x0700,
+            Self::BMODE(n) => 0x0800 | n as u16,
+            Self::CCOL(nn) => 0x0900 | nn as u16,
+        }
+    }
+
+    // the opcode `platform` decodes to this, where it differs from encode
+    pub fn encode_for(&self, platform: Platform) -> u16 {
+        match *self {
+            Self::SCU(n) if platform.supports_megachip() => 0x00B0 | n as u16,
+            _ => self.encode(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidInstruction;
+
+impl fmt::Display for InvalidInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid instruction")
+    }
+}
+
+impl core::error::Error for InvalidInstruction {}
+
+// a V register: V0-VF, also written with a hex prefix like V0xa
+fn parse_reg(s: &str) -> Option<u8> {
+    let s = s.strip_prefix(['V', 'v'])?;
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    u8::from_str_radix(digits, 16).ok().filter(|&x| x < 16 && !digits.is_empty())
+}
+
+// a number of at most `max`: 0x-prefixed or #-prefixed hex, or decimal
+fn parse_num(s: &str, max: u16) -> Option<u16> {
+    let value = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix('#')) {
+        u16::from_str_radix(hex, 16).ok()?
+    } else {
+        s.parse().ok()?
+    };
+    (value <= max).then_some(value)
+}
+
+// the syntax Display writes. mnemonics and the named operands are case
+// insensitive, and SHR/SHL may leave out their second register
+impl FromStr for Instruction {
+    type Err = InvalidInstruction;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let (mnemonic, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        // no allocation under no_std, the longest mnemonic is 7 letters
+        let mut upper = [0; 8];
+        let upper = upper.get_mut(..mnemonic.len()).ok_or(InvalidInstruction)?;
+        upper.copy_from_slice(mnemonic.as_bytes());
+        upper.make_ascii_uppercase();
+        let mnemonic = core::str::from_utf8(upper).map_err(|_| InvalidInstruction)?;
+
+        let mut ops = [""; 4];
+        let mut count = 0;
+        if !rest.trim().is_empty() {
+            for op in rest.split(',') {
+                // "SHR V1 {, V2}" lists its optional operand in braces
+                let op = op.trim().trim_matches(['{', '}']).trim();
+                *ops.get_mut(count).ok_or(InvalidInstruction)? = op;
+                count += 1;
+            }
+        }
+        let ops = &ops[..count];
+
+        let reg = |s: &str| parse_reg(s).ok_or(InvalidInstruction);
+        let addr = |s: &str| parse_num(s, 0xFFF).ok_or(InvalidInstruction);
+        let byte = |s: &str| parse_num(s, 0xFF).map(|v| v as u8).ok_or(InvalidInstruction);
+        let nibble = |s: &str| parse_num(s, 0xF).map(|v| v as u8).ok_or(InvalidInstruction);
+        let is = |s: &str, name: &str| s.eq_ignore_ascii_case(name);
+        // "Vx-Vy", the register range XO-CHIP loads and stores
+        let range = |s: &str| match s.split_once('-') {
+            Some((x, y)) => Ok((reg(x.trim())?, reg(y.trim())?)),
+            None => Err(InvalidInstruction),
+        };
+
+        let instruction = match (mnemonic, ops) {
+            ("RAW0", []) => Self::RAW0,
+            ("CLS", []) => Self::CLS,
+            ("RET", []) => Self::RET,
+            ("SCR", []) => Self::SCR,
+            ("SCL", []) => Self::SCL,
+            ("EXIT", []) => Self::EXIT,
+            ("LOW", []) => Self::LOW,
+            ("HIGH", []) => Self::HIGH,
+            ("AUDIO", []) => Self::AUDIO,
+            ("BGC", []) => Self::BGC,
+            ("MEGAOFF", []) => Self::MEGAOFF,
+            ("MEGAON", []) => Self::MEGAON,
+            ("STOPSND", []) => Self::STOPSND,
+            ("SYS", [nnn]) => Self::SYS(addr(nnn)?),
+            ("CALL", [nnn]) => Self::CALL(addr(nnn)?),
+            ("JP", [v0, nnn]) if is(v0, "V0") => Self::JP(JPType::FromV0(addr(nnn)?)),
+            ("JP", [target]) => match target.split_once('+') {
+                Some((v0, nnn)) if is(v0.trim(), "V0") => {
+                    Self::JP(JPType::FromV0(addr(nnn.trim())?))
+                }
+                _ => Self::JP(JPType::Addr(addr(target)?)),
+            },
+            ("SE", [x, y]) if parse_reg(y).is_some() => Self::SE(reg(x)?, SEType::Reg(reg(y)?)),
+            ("SE", [x, kk]) => Self::SE(reg(x)?, SEType::Byte(byte(kk)?)),
+            ("SNE", [x, y]) if parse_reg(y).is_some() => Self::SNE(reg(x)?, SEType::Reg(reg(y)?)),
+            ("SNE", [x, kk]) => Self::SNE(reg(x)?, SEType::Byte(byte(kk)?)),
+            ("LD", [i, long]) if is(i, "I") && is(long, "LONG") => Self::LD(0, LDType::LongAddr),
+            ("LD", [i, nnn]) if is(i, "I") => Self::LD(0, LDType::Addr(addr(nnn)?)),
+            ("LD", [b, x]) if is(b, "B") => Self::LD(reg(x)?, LDType::B),
+            ("LD", [f, x]) if is(f, "F") => Self::LD(reg(x)?, LDType::F),
+            ("LD", [hf, x]) if is(hf, "HF") => Self::LD(reg(x)?, LDType::HF),
+            ("LD", [r, x]) if is(r, "R") => Self::LD(reg(x)?, LDType::ToR),
+            ("LD", [dt, x]) if is(dt, "DT") => Self::LD(reg(x)?, LDType::ToDT),
+            ("LD", [st, x]) if is(st, "ST") => Self::LD(reg(x)?, LDType::ToST),
+            ("LD", [i, xy]) if is(i, "[I]") && xy.contains('-') => {
+                let (x, y) = range(xy)?;
+                Self::LD(x, LDType::ToIRange(y))
+            }
+            ("LD", [i, x]) if is(i, "[I]") => Self::LD(reg(x)?, LDType::ToI),
+            ("LD", [xy, i]) if is(i, "[I]") && xy.contains('-') => {
+                let (x, y) = range(xy)?;
+                Self::LD(x, LDType::FromIRange(y))
+            }
+            ("LD", [x, i]) if is(i, "[I]") => Self::LD(reg(x)?, LDType::FromI),
+            ("LD", [x, dt]) if is(dt, "DT") => Self::LD(reg(x)?, LDType::FromDT),
+            ("LD", [x, k]) if is(k, "K") => Self::LD(reg(x)?, LDType::KeyPress),
+            ("LD", [x, r]) if is(r, "R") => Self::LD(reg(x)?, LDType::FromR),
+            ("LD", [x, y]) if parse_reg(y).is_some() => Self::LD(reg(x)?, LDType::Reg(reg(y)?)),
+            ("LD", [x, kk]) => Self::LD(reg(x)?, LDType::Byte(byte(kk)?)),
+            ("ADD", [i, x]) | ("ADD", [x, i]) if is(i, "I") => Self::ADD(reg(x)?, AddType::I),
+            ("ADD", [x, y]) if parse_reg(y).is_some() => Self::ADD(reg(x)?, AddType::Reg(reg(y)?)),
+            ("ADD", [x, kk]) => Self::ADD(reg(x)?, AddType::Byte(byte(kk)?)),
+            ("OR", [x, y]) => Self::OR(reg(x)?, reg(y)?),
+            ("AND", [x, y]) => Self::AND(reg(x)?, reg(y)?),
+            ("XOR", [x, y]) => Self::XOR(reg(x)?, reg(y)?),
+            ("SUB", [x, y]) => Self::SUB(reg(x)?, reg(y)?),
+            ("SUBN", [x, y]) => Self::SUBN(reg(x)?, reg(y)?),
+            ("SHR", [x, y]) => Self::SHR(reg(x)?, reg(y)?),
+            ("SHR", [x]) => Self::SHR(reg(x)?, reg(x)?),
+            ("SHL", [x, y]) => Self::SHL(reg(x)?, reg(y)?),
+            ("SHL", [x]) => Self::SHL(reg(x)?, reg(x)?),
+            ("RND", [x, kk]) => Self::RND(reg(x)?, byte(kk)?),
+            ("DRW", [x, y, n]) => Self::DRW(reg(x)?, reg(y)?, nibble(n)?),
+            ("SKP", [x]) => Self::SKP(reg(x)?),
+            ("SKNP", [x]) => Self::SKNP(reg(x)?),
+            ("SKP2", [x]) => Self::SKP2(reg(x)?),
+            ("SKNP2", [x]) => Self::SKNP2(reg(x)?),
+            ("SCD", [n]) => Self::SCD(nibble(n)?),
+            ("SCU", [n]) => Self::SCU(nibble(n)?),
+            ("PLANE", [n]) => Self::PLANE(nibble(n)?),
+            ("PITCH", [x]) => Self::PITCH(reg(x)?),
+            ("COL", [x, y, n]) => Self::COL(reg(x)?, reg(y)?, nibble(n)?),
+            ("LDHI", [i, nn, long]) if is(i, "I") && is(long, "LONG") => Self::LDHI(byte(nn)?),
+            ("LDPAL", [nn]) => Self::LDPAL(byte(nn)?),
+            ("SPRW", [nn]) => Self::SPRW(byte(nn)?),
+            ("SPRH", [nn]) => Self::SPRH(byte(nn)?),
+            ("ALPHA", [nn]) => Self::ALPHA(byte(nn)?),
+            ("DIGISND", [n]) => Self::DIGISND(nibble(n)?),
+            ("BMODE", [n]) => Self::BMODE(nibble(n)?),
+            ("CCOL", [nn]) => Self::CCOL(byte(nn)?),
+            _ => return Err(InvalidInstruction),
+        };
+
+        Ok(instruction)
+    }
 }
 
-pub struct Cpu {
-    pub ram: Vec<u8>,
-    pub stack: Vec<u16>,
+// what happened during a single step, so frontends can react to machine
+// events without having to inspect the cpu state after every instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StepOutcome {
+    Continue,
+    DisplayChanged,
+    WaitingForKey,
+    SoundStarted,
+    SoundStopped,
+    Halted,
+    SysCall(u16),
+    ResolutionChanged,
+    // an illegal opcode under OpcodePolicy::Trap, pc still points at it
+    Trapped(u16),
+}
+
+// summary of a single 60 Hz frame, see Cpu::run_frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameOutcome {
+    pub instructions: usize,
+    pub display_changed: bool,
+    pub resolution_changed: bool,
+    pub waiting_for_key: bool,
+    pub halted: bool,
+    // the illegal opcode the frame stopped on under OpcodePolicy::Trap
+    pub trapped: Option<u16>,
+    pub sound_playing: bool,
+}
+
+// how many of the last executed instructions History keeps
+pub const HISTORY_LEN: usize = 8;
+
+// the last HISTORY_LEN instructions the cpu executed, as (address, opcode),
+// and the platform they are decoded for
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct History {
+    entries: [(u16, u16); HISTORY_LEN],
+    len: usize,
+    next: usize,
+    platform: Platform,
+}
+
+impl History {
+    pub fn new(platform: Platform) -> History {
+        History {
+            platform,
+            ..History::default()
+        }
+    }
+
+    pub fn push(&mut self, addr: u16, opcode: u16) {
+        self.entries[self.next] = (addr, opcode);
+        self.next = (self.next + 1) % HISTORY_LEN;
+        self.len = (self.len + 1).min(HISTORY_LEN);
+    }
+
+    // oldest first
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u16)> + '_ {
+        let start = (self.next + HISTORY_LEN - self.len) % HISTORY_LEN;
+        (0..self.len).map(move |i| self.entries[(start + i) % HISTORY_LEN])
+    }
+
+    pub fn display(&self, syntax: Syntax) -> HistoryDisplay<'_> {
+        HistoryDisplay {
+            history: self,
+            syntax,
+        }
+    }
+}
 
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display(Syntax::default()).fmt(f)
+    }
+}
+
+// a History printed in a given syntax, one instruction per line, oldest
+// first
+pub struct HistoryDisplay<'a> {
+    history: &'a History,
+    syntax: Syntax,
+}
+
+impl fmt::Display for HistoryDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (addr, opcode) in self.history.iter() {
+            write!(f, "\n  {:#05x}  {:#06x}", addr, opcode)?;
+            if let Some(ins) = Instruction::decode_for(opcode, self.history.platform) {
+                write!(f, "  {}", ins.display(self.syntax))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub const DEFAULT_PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+];
+
+type Plane = [bool; MAX_WIDTH * MAX_HEIGHT];
+// with std the stack grows as calls nest, without it it's a fixed array
+#[cfg(feature = "std")]
+type Stack = Vec<u16>;
+#[cfg(not(feature = "std"))]
+type Stack = [u16; MAX_STACK_DEPTH];
+type MappedBytes = ([u8; 2 * VIP_STACK_SLOTS], [u8; 16], [u8; 256]);
+
+// where the VIP interpreter keeps its state in the top of its 4K
+const VIP_CDP1802_STACK: u16 = 0xECF;
+const VIP_STACK_ADDR: usize = 0xEA0;
+// return addresses that fit before the V registers
+const VIP_STACK_SLOTS: usize = 16;
+const VIP_VX_ADDR: usize = 0xEF0;
+const VIP_DISPLAY_ADDR: usize = 0xF00;
+// a subroutine that hasn't returned after this many 1802 instructions
+// probably never will
+const MACHINE_CODE_STEP_LIMIT: usize = 1_000_000;
+
+// CHIP-8X colours are per zone of 8x1 pixels on the 64x32 display
+const COLOUR_COLUMNS: usize = 8;
+const COLOUR_ROWS: usize = 32;
+
+// the VP-590 background cycles blue, black, green, red
+pub const CHIP8X_BACKGROUNDS: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x80, 0xFF],
+    [0x00, 0x00, 0x00, 0xFF],
+    [0x00, 0x80, 0x00, 0xFF],
+    [0x80, 0x00, 0x00, 0xFF],
+];
+
+// foreground colours are 3 bits: red 1, blue 2, green 4
+pub const CHIP8X_FOREGROUNDS: [[u8; 4]; 8] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0xFF, 0x00, 0x00, 0xFF],
+    [0x00, 0x00, 0xFF, 0xFF],
+    [0xFF, 0x00, 0xFF, 0xFF],
+    [0x00, 0xFF, 0x00, 0xFF],
+    [0xFF, 0xFF, 0x00, 0xFF],
+    [0x00, 0xFF, 0xFF, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+];
+const CHIP8X_RED: u8 = 1;
+
+// until a rom loads its own pattern the buzzer plays a 500 Hz square wave
+pub const DEFAULT_PATTERN: [u8; 16] = [0xF0; 16];
+pub const DEFAULT_PITCH: u8 = 64;
+
+// MEMORY is the size of `ram`. it defaults to the largest platform, a cpu
+// that only runs 4K platforms can be built as Cpu<R, 0x1000> with with_memory
+pub struct Cpu<R: RandomSource = XorShift, const MEMORY: usize = MAX_MEMORY> {
+    pub platform: Platform,
+    // only memory_size() bytes are usable
+    pub ram: [u8; MEMORY],
+    // return addresses, the first `sp` are in use
+    pub stack: Stack,
+    // CALL past this many overflows, None for no limit
+    pub stack_depth: Option<usize>,
+
+    pub load_addr: u16, // where roms are loaded and execution starts
     pub pc: u16,       // program counter
     pub ir: u16,       // index register
-    pub vx: Vec<u8>,   // V-registers
+    pub vx: [u8; 16],  // V-registers
     pub st: u8,        // sound timer
     pub dt: u8,        // delay timer
-    pub sp: u8,        // stack pointer
-    pub kp: Vec<bool>, // key pressed
-
-    pub height: u8,
-    pub width: u8,
-    pub screen: Vec<bool>,
+    pub sp: usize,     // stack pointer
+    pub kp: [bool; 16], // key pressed
+
+    pub height: u16,
+    pub width: u16,
+    pub hires: bool,
+    // row-major, `width` pixels per row, only width * height are in use
+    pub screen: Plane,
+    pub plane2: Plane, // XO-CHIP second bitplane
+    pub planes: u8, // bitmask of the planes drawing and scrolling affect
+    // rgba colours for (plane2 << 1) | screen
+    pub palette: [[u8; 4]; 4],
+    pub rpl: [u8; 16], // SUPER-CHIP user flags
+    // XO-CHIP audio: 128 1-bit samples played while st > 0, and the pitch
+    // register that sets their playback rate
+    pub pattern: [u8; 16],
+    pub pitch: u8,
+    // CHIP-8X: index into CHIP8X_BACKGROUNDS, the CHIP8X_FOREGROUNDS index
+    // of every 8x1 pixel zone, and the second keypad
+    pub background: u8,
+    pub colours: [u8; COLOUR_COLUMNS * COLOUR_ROWS],
+    pub kp2: [bool; 16],
+    // MegaChip: when enabled its framebuffer replaces the planes entirely.
+    // only allocated for that platform, without std the caller attaches one
+    pub mega: Option<megachip::Buffer>,
+
+    // the fonts FX29 and FX30 point into, and where they are loaded
+    pub font: SmallFont,
+    pub big_font: Option<BigFont>,
+    pub font_addr: u16,
+    pub big_font_addr: u16,
+
+    pub quirks: Quirks,
+    pub memory_policy: MemoryPolicy,
+    pub opcode_policy: OpcodePolicy,
+    pub rng: R,
+    // how print_ram, dump_state and skipped opcode reports write instructions
+    pub syntax: Syntax,
+
+    // when set, frames are measured in VIP machine cycles instead of
+    // instructions
+    pub timing: Option<VipTiming>,
+    // keep the stack, V registers and display in ram where the VIP has them,
+    // for roms that read or patch them directly
+    pub memory_mapped: bool,
+
+    history: History,
+
+    // set by DXYN under the display_wait quirk, ends the current frame
+    vblank_wait: bool,
+    // spent by the last 0NNN in 1802 code
+    machine_cycles: u32,
 }
 
 impl Cpu {
     pub fn init() -> Cpu {
-        let mut ret = Cpu {
-            ram: vec![0x00; 4096],
-            stack: vec![0x0000; 16],
+        Cpu::for_platform(Platform::default())
+    }
+
+    // under std the random source is seeded from os entropy, otherwise it
+    // starts from a fixed seed. use Cpu::with_platform to inject a different one
+    pub fn for_platform(platform: Platform) -> Cpu {
+        #[cfg(feature = "std")]
+        let rng = XorShift::from_entropy();
+        #[cfg(not(feature = "std"))]
+        let rng = XorShift::default();
+
+        Cpu::with_platform(platform, rng)
+    }
+}
+
+impl<R: RandomSource> Cpu<R> {
+    pub fn with_rng(rng: R) -> Cpu<R> {
+        Cpu::with_platform(Platform::default(), rng)
+    }
+
+    pub fn with_platform(platform: Platform, rng: R) -> Cpu<R> {
+        Cpu::with_memory(platform, rng)
+    }
+}
+
+impl<R: RandomSource, const MEMORY: usize> Cpu<R, MEMORY> {
+    // like with_platform, with MEMORY bytes of ram. panics if the platform
+    // has more memory than that
+    pub fn with_memory(platform: Platform, rng: R) -> Cpu<R, MEMORY> {
+        assert!(
+            platform.memory_size() <= MEMORY,
+            "{} needs {:#x} bytes of ram, the cpu has {:#x}",
+            platform,
+            platform.memory_size(),
+            MEMORY
+        );
+        let (width, height) = platform.resolution();
 
-            pc: 0x200,
+        let mut ret = Cpu {
+            platform,
+            ram: [0x00; MEMORY],
+            #[cfg(feature = "std")]
+            stack: vec![0x0000; VIP_STACK_SLOTS],
+            #[cfg(not(feature = "std"))]
+            stack: [0x0000; MAX_STACK_DEPTH],
+            stack_depth: platform.stack_depth(),
+
+            load_addr: platform.start_addr(),
+            pc: platform.start_addr(),
             ir: 0,
-            vx: vec![0; 16],
+            vx: [0; 16],
             st: 60,
             dt: 60,
             sp: 0,
-            kp: vec![false; 16],
-
-            height: 32,
-            width: 64,
-            screen: vec![false; 64 * 32],
+            kp: [false; 16],
+
+            height,
+            width,
+            hires: false,
+            screen: [false; MAX_WIDTH * MAX_HEIGHT],
+            plane2: [false; MAX_WIDTH * MAX_HEIGHT],
+            planes: 0x1,
+            palette: DEFAULT_PALETTE,
+            rpl: [0; 16],
+            pattern: DEFAULT_PATTERN,
+            pitch: DEFAULT_PITCH,
+            background: 0,
+            colours: [CHIP8X_RED; COLOUR_COLUMNS * COLOUR_ROWS],
+            kp2: [false; 16],
+            mega: None,
+
+            font: platform.font(),
+            big_font: platform.big_font(),
+            font_addr: platform.font_addr(),
+            big_font_addr: platform.big_font_addr(),
+
+            quirks: platform.quirks(),
+            memory_policy: MemoryPolicy::default(),
+            opcode_policy: OpcodePolicy::default(),
+            rng,
+            syntax: Syntax::default(),
+
+            timing: None,
+            memory_mapped: false,
+
+            history: History::new(platform),
+
+            vblank_wait: false,
+            machine_cycles: 0,
         };
 
+        #[cfg(feature = "std")]
+        if platform.supports_megachip() {
+            ret.mega = Some(Box::new(MegaChip::new()));
+        }
         ret.load_font();
 
         ret
     }
 
-    fn load_font(&mut self) {
-        let font = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-        ];
+    // copies the fonts to their addresses, call again after changing them
+    pub fn load_font(&mut self) {
+        let glyphs = self.font.glyphs();
+        let addr = self.font_addr as usize;
+        self.ram[addr..(addr + glyphs.len())].copy_from_slice(glyphs);
 
-        self.ram[0x050..0x0A0].copy_from_slice(&font);
+        if let Some(font) = self.big_font {
+            let glyphs = font.glyphs();
+            let addr = self.big_font_addr as usize;
+            self.ram[addr..(addr + glyphs.len())].copy_from_slice(glyphs);
+        }
     }
 
-    pub fn load_rom(&mut self, filename: &str) {
-        let mut ram_addr: usize = 0x200;
-        let mut filepath: String = String::from("./roms/");
-        filepath.push_str(filename);
+    // how much of `ram` the current platform can address
+    pub fn memory_size(&self) -> usize {
+        self.platform.memory_size().min(MEMORY)
+    }
 
-        let buffer = BufReader::new(File::open(filepath).unwrap());
+    // the index into `ram` of the byte at `addr`, under the memory policy
+    fn resolve(&self, addr: usize) -> usize {
+        self.memory_policy.resolve(addr, self.memory_size())
+    }
 
-        for byte_or_error in buffer.bytes() {
-            let byte = byte_or_error.unwrap();
-            self.ram[ram_addr] = byte;
-            ram_addr += 1;
+    // `addr` moved forward by `n` bytes, for the program counter and I
+    fn advance(&self, addr: u16, n: usize) -> u16 {
+        match self.memory_policy {
+            MemoryPolicy::Fault => addr.wrapping_add(n as u16),
+            _ => self.resolve(addr as usize + n) as u16,
         }
     }
 
-    pub fn print_ram(&mut self) {
-        let start_pc = self.pc;
-        self.pc = 0x200;
+    #[cfg(feature = "std")]
+    pub fn load_rom_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let file = File::open(path)?;
+        self.load_rom_from_reader(BufReader::new(file))
+    }
 
-        loop {
-            if self.pc as usize >= self.ram.len() {
-                break;
-            }
+    #[cfg(feature = "std")]
+    pub fn load_rom_from_reader(&mut self, mut reader: impl Read) -> Result<()> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        self.load_rom_from_bytes(&rom)
+    }
+
+    // copies the rom to `load_addr` and points the program counter at it
+    pub fn load_rom_from_bytes(&mut self, rom: &[u8]) -> Result<()> {
+        let ram_addr = self.load_addr as usize;
+        if ram_addr >= self.memory_size() {
+            return Err(Error::MemoryOutOfBounds { addr: ram_addr });
+        }
+        let max = self.memory_size() - ram_addr;
+        if rom.len() > max {
+            return Err(Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
 
-            let b1 = self.ram[self.pc as usize] as u16;
-            let b2 = self.ram[(self.pc + 1) as usize] as u16;
-            print!("{:#06x}\t({:#06x})\t", self.pc, (b1 << 8) | b2);
+        // the entry point moves with the rom, it has to land inside memory
+        let entry = self.platform.entry_point(rom) as usize;
+        let start = self.platform.start_addr() as usize;
+        let pc = (ram_addr + entry)
+            .checked_sub(start)
+            .filter(|&pc| pc < self.memory_size())
+            .ok_or(Error::MemoryOutOfBounds {
+                addr: (ram_addr + entry).saturating_sub(start),
+            })?;
 
-            let bytes = self.fetch();
+        self.ram[ram_addr..(ram_addr + rom.len())].copy_from_slice(rom);
+        self.pc = pc as u16;
+
+        Ok(())
+    }
+
+    // lists the words from load_addr up to the first 0000
+    #[cfg(feature = "std")]
+    pub fn print_ram(&self) {
+        let mut addr = self.load_addr as usize;
+
+        while addr + 1 < self.memory_size() {
+            let bytes = u16::from_be_bytes([self.ram[addr], self.ram[addr + 1]]);
             if bytes == 0x0000 {
                 break;
             }
+            print!("{:#06x}\t({:#06x})\t", addr, bytes);
+            addr += 2;
 
-            let ins = Instruction::decode(bytes);
-
-            if ins.is_some() {
-                println!("{}", ins.unwrap().to_string());
-            }
+            let Some(ins) = Instruction::decode_for(bytes, self.platform) else {
+                println!();
+                continue;
+            };
+            // a long load's address is part of it, not the next instruction
+            let long = match ins {
+                Instruction::LD(_, LDType::LongAddr) if addr + 1 < self.memory_size() => {
+                    addr += 2;
+                    Some(u16::from_be_bytes([self.ram[addr - 2], self.ram[addr - 1]]))
+                }
+                _ => None,
+            };
+            println!("{}", ins.display(self.syntax).with_long(long));
         }
-
-        self.pc = start_pc;
     }
 
+    #[cfg(feature = "std")]
     pub fn dump_state(&self) {
         println!("\nregisters:");
         for i in 0..self.vx.len() {
@@ -271,11 +963,20 @@ impl Cpu {
         }
 
         println!("\nstack:");
-        for i in 0..self.stack.len() {
-            println!("V{} | {:#x} | ", i, self.stack[i]);
+        for i in 0..self.sp {
+            println!("{} | {:#x} | ", i, self.stack[i]);
         }
 
         println!("\nprogram counter: {:#x}", self.pc);
+        let addr = self.pc as usize;
+        if addr + 1 < self.memory_size() {
+            let opcode = u16::from_be_bytes([self.ram[addr], self.ram[addr + 1]]);
+            match Instruction::decode_for(opcode, self.platform) {
+                Some(ins) => println!("next instruction: {}", ins.display(self.syntax)),
+                None => println!("next instruction: illegal opcode {:#06x}", opcode),
+            }
+        }
+        println!("\nrecent instructions:{}", self.history.display(self.syntax));
         println!("\nindex register: {:#x}", self.ir);
 
         println!("\nkeypad:");
@@ -283,32 +984,186 @@ impl Cpu {
             println!("V{} | {} | ", i, self.kp[i]);
         }
 
-        println!("");
+        println!();
     }
 
+    // `frame` is an rgba buffer of width * height pixels
     pub fn draw(&self, frame: &mut [u8]) {
-        for (idx, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let pixel_on = self.screen[idx];
+        if let Some(mega) = self.mega.as_deref().filter(|mega| mega.enabled) {
+            mega.draw(frame);
+            return;
+        }
+        if self.platform.supports_chip8x() {
+            self.draw_chip8x(frame);
+            return;
+        }
+
+        let len = self.width as usize * self.height as usize;
+        for (idx, pixel) in frame.chunks_exact_mut(4).take(len).enumerate() {
+            let color = (self.screen[idx] as usize) | ((self.plane2[idx] as usize) << 1);
+            pixel.copy_from_slice(&self.palette[color]);
+        }
+    }
 
-            let rgba = if !pixel_on {
-                [0x00, 0x00, 0x00, 0xFF]
+    // lit pixels take the colour of their zone, the rest show the background
+    fn draw_chip8x(&self, frame: &mut [u8]) {
+        let width = self.width as usize;
+        let len = width * self.height as usize;
+        let background = CHIP8X_BACKGROUNDS[self.background as usize % 4];
+        for (idx, pixel) in frame.chunks_exact_mut(4).take(len).enumerate() {
+            if self.screen[idx] {
+                let (col, row) = (idx % width, idx / width);
+                let zone = (row % COLOUR_ROWS) * COLOUR_COLUMNS + (col / 8) % COLOUR_COLUMNS;
+                pixel.copy_from_slice(&CHIP8X_FOREGROUNDS[self.colours[zone] as usize & 0x7]);
             } else {
-                [0xFF, 0xFF, 0xFF, 0xFF]
-            };
+                pixel.copy_from_slice(&background);
+            }
+        }
+    }
+
+    pub fn fetch(&mut self) -> Result<u16> {
+        let addr = self.pc as usize;
+        if self.memory_policy == MemoryPolicy::Fault && addr + 1 >= self.memory_size() {
+            return Err(Error::MemoryOutOfBounds { addr });
+        }
+
+        let n1: u16 = self.ram[self.resolve(addr)] as u16;
+        let n2: u16 = self.ram[self.resolve(addr + 1)] as u16;
+        self.pc = self.advance(self.pc, 2);
 
-            pixel.copy_from_slice(&rgba);
+        Ok((n1 << 8) | n2)
+    }
+
+    // fetches, decodes and executes a single instruction. a raw 0x0000 is
+    // treated as an illegal opcode, since it almost always means the
+    // program ran off the end of its code into zeroed memory
+    pub fn step(&mut self) -> Result<StepOutcome> {
+        let addr = self.pc;
+        let opcode = self.fetch()?;
+
+        let instruction = match Instruction::decode_for(opcode, self.platform) {
+            Some(Instruction::RAW0) | None => return self.on_illegal(addr, opcode),
+            Some(instruction) => instruction,
+        };
+        self.history.push(addr, opcode);
+
+        if self.memory_mapped {
+            self.execute_mapped(instruction)
+        } else {
+            self.execute(instruction)
+        }
+    }
+
+    // the instructions executed before the current one
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    fn on_illegal(&mut self, addr: u16, opcode: u16) -> Result<StepOutcome> {
+        match self.opcode_policy {
+            OpcodePolicy::Halt => Err(Error::IllegalOpcode {
+                addr,
+                opcode,
+                history: self.history,
+            }),
+            OpcodePolicy::Skip => {
+                log::warn!(
+                    "skipped illegal opcode {:#06x} at {:#05x}, after:{}",
+                    opcode,
+                    addr,
+                    self.history.display(self.syntax)
+                );
+                Ok(StepOutcome::Continue)
+            }
+            OpcodePolicy::Trap => {
+                self.pc = addr;
+                Ok(StepOutcome::Trapped(opcode))
+            }
+        }
+    }
+
+    // runs one 60 Hz frame: up to `instructions_per_frame` instructions,
+    // or a frame's worth of machine cycles with the VIP timing model,
+    // followed by a single tick of the delay and sound timers. the frame
+    // ends early if the program halts, blocks waiting for a key, or draws a
+    // sprite under the display_wait quirk
+    pub fn run_frame(&mut self, instructions_per_frame: usize) -> Result<FrameOutcome> {
+        let mut frame = FrameOutcome::default();
+        let mut waited = false;
+
+        loop {
+            match &self.timing {
+                Some(timing) if timing.frame_done() => break,
+                None if frame.instructions == instructions_per_frame => break,
+                _ => {}
+            }
+
+            let cycles = self.timing.map(|_| self.next_instruction_cycles());
+            let outcome = self.step()?;
+            frame.instructions += 1;
+            if let (Some(timing), Some(cycles)) = (&mut self.timing, cycles) {
+                timing.charge(cycles + core::mem::take(&mut self.machine_cycles));
+            }
+
+            match outcome {
+                StepOutcome::ResolutionChanged => {
+                    frame.display_changed = true;
+                    frame.resolution_changed = true;
+                }
+                StepOutcome::DisplayChanged => {
+                    frame.display_changed = true;
+                    if self.vblank_wait {
+                        self.vblank_wait = false;
+                        waited = true;
+                        break;
+                    }
+                }
+                StepOutcome::WaitingForKey => {
+                    frame.waiting_for_key = true;
+                    waited = true;
+                    break;
+                }
+                StepOutcome::Halted => {
+                    frame.halted = true;
+                    waited = true;
+                    break;
+                }
+                StepOutcome::Trapped(opcode) => {
+                    frame.trapped = Some(opcode);
+                    waited = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(timing) = &mut self.timing {
+            timing.end_frame(waited);
         }
+        self.tick_timers();
+        frame.sound_playing = self.st > 0;
+
+        Ok(frame)
     }
 
-    pub fn fetch(&mut self) -> u16 {
-        let n1: u16 = self.ram[self.pc as usize] as u16;
-        let n2: u16 = self.ram[(self.pc + 1) as usize] as u16;
-        self.pc += 2;
+    // the cost of the instruction at pc, before it runs
+    fn next_instruction_cycles(&self) -> u32 {
+        let addr = self.pc as usize;
+        if addr + 1 >= self.memory_size() {
+            return 0;
+        }
+
+        let opcode = u16::from_be_bytes([self.ram[addr], self.ram[addr + 1]]);
+        Instruction::decode_for(opcode, self.platform)
+            .map_or(0, |instruction| self.instruction_cycles(&instruction))
+    }
 
-        (n1 << 8) | n2
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
     }
 
-    pub fn execute(&mut self, instruction: Instruction) -> Option<u8> {
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome> {
         match instruction {
             Instruction::ADD(x, AddType::Byte(kk)) => self.on_add_byte(x, kk),
             Instruction::ADD(x, AddType::I) => self.on_add_i(x),
@@ -331,7 +1186,7 @@ impl Cpu {
             Instruction::LD(x, LDType::ToI) => self.on_ld_to_i(x),
             Instruction::LD(x, LDType::ToST) => self.on_ld_to_st(x),
             Instruction::OR(x, y) => self.on_or(x, y),
-            Instruction::RAW0 => None,
+            Instruction::RAW0 => Ok(StepOutcome::Halted),
             Instruction::RET => self.on_ret(),
             Instruction::RND(x, kk) => self.on_rnd(x, kk),
             Instruction::SE(x, SEType::Byte(kk)) => self.on_se_byte(x, kk),
@@ -346,348 +1201,961 @@ impl Cpu {
             Instruction::SUBN(x, y) => self.on_subn(x, y),
             Instruction::SYS(nnn) => self.on_sys(nnn),
             Instruction::XOR(x, y) => self.on_xor(x, y),
+            Instruction::SCD(n) => self.on_scroll(0, n as isize),
+            Instruction::SCU(n) => self.on_scroll(0, -(n as isize)),
+            Instruction::SCR => self.on_scroll(4, 0),
+            Instruction::SCL => self.on_scroll(-4, 0),
+            Instruction::PLANE(n) => self.on_plane(n),
+            Instruction::AUDIO => self.on_audio(),
+            Instruction::PITCH(x) => self.on_pitch(x),
+            Instruction::BGC => self.on_bgc(),
+            Instruction::COL(x, y, n) => self.on_col(x, y, n),
+            Instruction::SKP2(x) => self.on_skp2(x),
+            Instruction::SKNP2(x) => self.on_sknp2(x),
+            Instruction::MEGAOFF => self.on_mega(false),
+            Instruction::MEGAON => self.on_mega(true),
+            Instruction::LDHI(nn) => self.on_ld_hi(nn),
+            Instruction::LDPAL(nn) => self.on_ld_pal(nn),
+            Instruction::SPRW(nn) => self.on_sprw(nn),
+            Instruction::SPRH(nn) => self.on_sprh(nn),
+            Instruction::ALPHA(nn) => self.on_alpha(nn),
+            Instruction::DIGISND(n) => self.on_digisnd(n),
+            Instruction::STOPSND => self.on_stopsnd(),
+            Instruction::BMODE(n) => self.on_bmode(n),
+            Instruction::CCOL(nn) => self.on_ccol(nn),
+            Instruction::LD(_, LDType::LongAddr) => self.on_ld_long(),
+            Instruction::LD(x, LDType::ToIRange(y)) => self.on_ld_to_i_range(x, y),
+            Instruction::LD(x, LDType::FromIRange(y)) => self.on_ld_from_i_range(x, y),
+            Instruction::EXIT => Ok(StepOutcome::Halted),
+            Instruction::LOW => self.on_resolution(false),
+            Instruction::HIGH => self.on_resolution(true),
+            Instruction::LD(x, LDType::HF) => self.on_ld_hf(x),
+            Instruction::LD(x, LDType::ToR) => self.on_ld_to_r(x),
+            Instruction::LD(x, LDType::FromR) => self.on_ld_from_r(x),
         }
     }
 
-    fn on_cls(&mut self) -> Option<u8> {
+    fn on_cls(&mut self) -> Result<StepOutcome> {
         // CLS
-        for x in &mut self.screen {
-            *x = false;
+        if let Some(mega) = self.mega.as_deref_mut().filter(|mega| mega.enabled) {
+            mega.clear();
+        }
+        for plane in self.selected_planes() {
+            self.plane_mut(plane).fill(false);
         }
 
-        None
+        Ok(StepOutcome::DisplayChanged)
     }
 
-    fn on_ret(&mut self) -> Option<u8> {
+    fn on_ret(&mut self) -> Result<StepOutcome> {
         // RET
-        self.pc = self.stack[self.sp as usize];
+        if self.sp == 0 {
+            return Err(Error::StackUnderflow { addr: self.pc.wrapping_sub(2) });
+        }
+
         self.sp -= 1;
+        self.pc = self.stack[self.sp];
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_sys(&mut self, nnn: u16) -> Option<u8> {
+    fn on_sys(&mut self, nnn: u16) -> Result<StepOutcome> {
         // SYS addr
-        println!("Instruction {:#x} is for a system call.", nnn);
+        if self.platform.supports_machine_code() {
+            return self.call_machine_code(nnn);
+        }
+
+        Ok(StepOutcome::SysCall(nnn))
+    }
+
+    // runs the 1802 subroutine at `addr` the way the VIP interpreter calls
+    // it: R3 is its program counter and it returns with SEP R4. the V
+    // registers and the display are copied into their places in ram for the
+    // duration of the call, and I and the CHIP-8 pc are in RA and R5
+    fn call_machine_code(&mut self, addr: u16) -> Result<StepOutcome> {
+        let mut cdp = Cdp1802::new();
+        cdp.r[2] = VIP_CDP1802_STACK;
+        cdp.r[3] = addr;
+        cdp.r[5] = self.pc;
+        cdp.r[0xA] = self.ir;
+        cdp.r[0xB] = VIP_DISPLAY_ADDR as u16;
+        cdp.p = 3;
+        cdp.x = 2;
+
+        let (_, _, display) = self.mapped_bytes();
+        self.store_mapped();
+
+        let memory_size = self.memory_size();
+        self.machine_cycles = 0;
+        let mut steps = 0;
+        while cdp.p != 4 {
+            if steps == MACHINE_CODE_STEP_LIMIT {
+                return Err(Error::MachineCodeTimeout { addr });
+            }
+            self.machine_cycles += cdp.step(&mut self.ram[..memory_size])?;
+            steps += 1;
+        }
+
+        self.load_mapped();
+        self.pc = cdp.r[5];
+        self.ir = cdp.r[0xA];
+
+        if self.mapped_bytes().2 == display {
+            Ok(StepOutcome::Continue)
+        } else {
+            Ok(StepOutcome::DisplayChanged)
+        }
+    }
+
+    // the display is only mapped while it's the VIP's 64x32
+    fn maps_display(&self) -> bool {
+        (self.width, self.height) == (64, 32) && !self.mega_enabled()
+    }
+
+    // stack_depth within what the stack can hold, and within the slots in
+    // ram when the stack is mapped there
+    fn max_stack_depth(&self) -> usize {
+        #[cfg(feature = "std")]
+        let capacity = usize::MAX;
+        #[cfg(not(feature = "std"))]
+        let capacity = MAX_STACK_DEPTH;
+
+        let depth = self.stack_depth.unwrap_or(capacity).min(capacity);
+        if self.memory_mapped {
+            depth.min(VIP_STACK_SLOTS)
+        } else {
+            depth
+        }
+    }
+
+    fn mega_enabled(&self) -> bool {
+        self.mega.as_ref().is_some_and(|mega| mega.enabled)
+    }
+
+    fn mega_mut(&mut self) -> Result<&mut MegaChip> {
+        let addr = self.pc.wrapping_sub(2);
+        self.mega.as_deref_mut().ok_or(Error::MegaChipUnavailable { addr })
+    }
+
+    // the stack, V registers and display as the VIP lays them out in ram:
+    // big-endian return addresses, then the 64x32 screen packed 8 pixels
+    // per byte, msb leftmost
+    fn mapped_bytes(&self) -> MappedBytes {
+        let mut stack = [0; 2 * VIP_STACK_SLOTS];
+        let depth = self.max_stack_depth();
+        for (bytes, addr) in stack.chunks_exact_mut(2).zip(self.stack.iter().take(depth)) {
+            bytes.copy_from_slice(&addr.to_be_bytes());
+        }
+
+        let mut display = [0; 256];
+        for (idx, &pixel) in self.screen[..64 * 32].iter().enumerate() {
+            display[idx / 8] |= (pixel as u8) << (7 - idx % 8);
+        }
+
+        (stack, self.vx, display)
+    }
+
+    fn store_mapped(&mut self) {
+        let (stack, vx, display) = self.mapped_bytes();
+        let len = 2 * self.max_stack_depth();
+        self.ram[VIP_STACK_ADDR..VIP_STACK_ADDR + len].copy_from_slice(&stack[..len]);
+        self.ram[VIP_VX_ADDR..VIP_VX_ADDR + 16].copy_from_slice(&vx);
+        if self.maps_display() {
+            self.ram[VIP_DISPLAY_ADDR..VIP_DISPLAY_ADDR + 256].copy_from_slice(&display);
+        }
+    }
+
+    fn load_mapped(&mut self) {
+        let depth = self.max_stack_depth();
+        let stack = &self.ram[VIP_STACK_ADDR..VIP_STACK_ADDR + 2 * depth];
+        for (addr, bytes) in self.stack.iter_mut().zip(stack.chunks_exact(2)) {
+            *addr = u16::from_be_bytes([bytes[0], bytes[1]]);
+        }
+        self.vx.copy_from_slice(&self.ram[VIP_VX_ADDR..VIP_VX_ADDR + 16]);
+        if self.maps_display() {
+            for (idx, pixel) in self.screen[..64 * 32].iter_mut().enumerate() {
+                *pixel = (self.ram[VIP_DISPLAY_ADDR + idx / 8] << (idx % 8)) & 0x80 != 0;
+            }
+        }
+    }
+
+    // runs `instruction` with the mapped structures shared with ram. bytes
+    // it changed through the structures are written to ram, everything else
+    // is read back from ram, where it may have written instead
+    fn execute_mapped(&mut self, instruction: Instruction) -> Result<StepOutcome> {
+        self.store_mapped();
+        let before = self.mapped_bytes();
+        let outcome = self.execute(instruction)?;
+        let after = self.mapped_bytes();
+
+        let regions = [
+            (VIP_STACK_ADDR, &before.0[..], &after.0[..]),
+            (VIP_VX_ADDR, &before.1[..], &after.1[..]),
+            (VIP_DISPLAY_ADDR, &before.2[..], &after.2[..]),
+        ];
+        for (start, before, after) in regions {
+            for (i, (old, new)) in before.iter().zip(after).enumerate() {
+                if old != new {
+                    self.ram[start + i] = *new;
+                }
+            }
+        }
+        self.load_mapped();
 
-        None
+        if outcome == StepOutcome::Continue && self.mapped_bytes().2 != before.2 {
+            Ok(StepOutcome::DisplayChanged)
+        } else {
+            Ok(outcome)
+        }
     }
 
-    fn on_jp(&mut self, nnn: u16) -> Option<u8> {
+    fn on_jp(&mut self, nnn: u16) -> Result<StepOutcome> {
         // JP addr
+        // a jump to itself is the usual way for a program to end, nothing
+        // after it can ever change the machine state again
+        let halted = nnn == self.pc.wrapping_sub(2);
         self.pc = nnn;
 
-        None
+        if halted {
+            Ok(StepOutcome::Halted)
+        } else {
+            Ok(StepOutcome::Continue)
+        }
     }
 
-    fn on_call(&mut self, nnn: u16) -> Option<u8> {
+    fn on_call(&mut self, nnn: u16) -> Result<StepOutcome> {
         // CALL addr
+        if self.sp >= self.max_stack_depth() {
+            return Err(Error::StackOverflow { addr: self.pc.wrapping_sub(2) });
+        }
+
+        #[cfg(feature = "std")]
+        if self.sp == self.stack.len() {
+            self.stack.push(0x0000);
+        }
+        self.stack[self.sp] = self.pc;
         self.sp += 1;
-        self.stack[self.sp as usize] = self.pc;
         self.pc = nnn;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_se_byte(&mut self, x: u8, kk: u8) -> Option<u8> {
+    fn on_se_byte(&mut self, x: u8, kk: u8) -> Result<StepOutcome> {
         // SE x, kk
         if self.vx[x as usize] == kk {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_sne_byte(&mut self, x: u8, kk: u8) -> Option<u8> {
+    fn on_sne_byte(&mut self, x: u8, kk: u8) -> Result<StepOutcome> {
         // SNE x, kk
         if self.vx[x as usize] != kk {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_se_reg(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_se_reg(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // SE x, y
         if self.vx[x as usize] == self.vx[y as usize] {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_byte(&mut self, x: u8, kk: u8) -> Option<u8> {
+    fn on_ld_byte(&mut self, x: u8, kk: u8) -> Result<StepOutcome> {
         // LD x, kk
         self.vx[x as usize] = kk;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_add_byte(&mut self, x: u8, kk: u8) -> Option<u8> {
+    fn on_add_byte(&mut self, x: u8, kk: u8) -> Result<StepOutcome> {
         // ADD x, kk
         self.vx[x as usize] = self.vx[x as usize].wrapping_add(kk);
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_reg(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_ld_reg(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // LD x, y
         self.vx[x as usize] = self.vx[y as usize];
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_or(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_or(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // OR x, y
         self.vx[x as usize] |= self.vx[y as usize];
+        if self.quirks.vf_reset {
+            self.vx[0xF] = 0;
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_and(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_and(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // AND x, y
         self.vx[x as usize] &= self.vx[y as usize];
+        if self.quirks.vf_reset {
+            self.vx[0xF] = 0;
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_xor(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_xor(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // XOR x, y
         self.vx[x as usize] ^= self.vx[y as usize];
+        if self.quirks.vf_reset {
+            self.vx[0xF] = 0;
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_add_reg(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_add_reg(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // ADD x, y
-        let sum: u16 = (self.vx[x as usize] as u16) + (self.vx[y as usize] as u16);
+        // VF is written last so it wins when x is 0xF
+        let (sum, carry) = self.vx[x as usize].overflowing_add(self.vx[y as usize]);
+        self.vx[x as usize] = sum;
+        self.vx[0xF] = carry as u8;
 
-        if sum & 0xFF00 > 0 {
-            self.vx[0xF] = 1;
-        }
-
-        self.vx[x as usize] = sum as u8;
-
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_sub(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_sub(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // SUB x, y
-        self.vx[0xF] = if self.vx[x as usize] > self.vx[y as usize] {
-            1
-        } else {
-            0
-        };
-
-        self.vx[x as usize] = if self.vx[0xF] == 1 {
-            self.vx[x as usize] - self.vx[y as usize]
-        } else {
-            self.vx[y as usize] - self.vx[x as usize]
-        };
+        let (diff, borrow) = self.vx[x as usize].overflowing_sub(self.vx[y as usize]);
+        self.vx[x as usize] = diff;
+        self.vx[0xF] = !borrow as u8;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_shr(&mut self, x: u8, _y: u8) -> Option<u8> {
+    fn on_shr(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // SHR x {, y}
-        if self.vx[x as usize].trailing_ones() > 1 {
-            self.vx[0xF] = 1
-        } else {
-            self.vx[0xF] = 0
-        };
-        self.vx[x as usize] = self.vx[x as usize] >> 1;
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let val = self.vx[src as usize];
+        self.vx[x as usize] = val >> 1;
+        self.vx[0xF] = val & 0x1;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_subn(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_subn(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // SUBN x, y
-        self.vx[0xF] = if self.vx[y as usize] > self.vx[x as usize] {
-            1
-        } else {
-            0
-        };
-
-        self.vx[x as usize] = if self.vx[0xF] == 0 {
-            self.vx[x as usize] - self.vx[y as usize]
-        } else {
-            self.vx[y as usize] - self.vx[x as usize]
-        };
+        let (diff, borrow) = self.vx[y as usize].overflowing_sub(self.vx[x as usize]);
+        self.vx[x as usize] = diff;
+        self.vx[0xF] = !borrow as u8;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_shl(&mut self, x: u8, _y: u8) -> Option<u8> {
-        // SHR x {, y}
-        if self.vx[x as usize].leading_ones() > 1 {
-            self.vx[0xF] = 1
-        } else {
-            self.vx[0xF] = 0
-        };
-        self.vx[x as usize] = self.vx[x as usize] << 1;
+    fn on_shl(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
+        // SHL x {, y}
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let val = self.vx[src as usize];
+        self.vx[x as usize] = val << 1;
+        self.vx[0xF] = val >> 7;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_sne_reg(&mut self, x: u8, y: u8) -> Option<u8> {
+    fn on_sne_reg(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
         // SNE x, y
         if self.vx[x as usize] != self.vx[y as usize] {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_addr(&mut self, nnn: u16) -> Option<u8> {
+    fn on_ld_addr(&mut self, nnn: u16) -> Result<StepOutcome> {
         // LD I, addr
         self.ir = nnn;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_jp_from0(&mut self, nnn: u16) -> Option<u8> {
+    fn on_jp_from0(&mut self, nnn: u16) -> Result<StepOutcome> {
         // JP V0, addr
-        self.pc = (self.vx[0] as u16) + nnn;
+        // with the jump_uses_vx quirk this is BXNN, a jump to XNN + VX
+        let reg = if self.quirks.jump_uses_vx {
+            (nnn >> 8) as usize
+        } else {
+            0
+        };
+        self.pc = (self.vx[reg] as u16) + nnn;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_rnd(&mut self, x: u8, kk: u8) -> Option<u8> {
+    fn on_rnd(&mut self, x: u8, kk: u8) -> Result<StepOutcome> {
         // RND x, kk
-        let mut rng = rand::rng();
-        self.vx[x as usize] = rng.random_range(0..=255) & kk;
+        self.vx[x as usize] = self.rng.random_byte() & kk;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_drw(&mut self, x: u8, y: u8, n: u8) -> Option<u8> {
+    fn on_drw(&mut self, x: u8, y: u8, n: u8) -> Result<StepOutcome> {
         // DRW x, y, n
-        self.vx[0xF] = 0;
-        for i in 0..n {
-            let sprite_byte: u8 = self.ram[(self.ir + i as u16) as usize];
-
-            for j in 0..8 {
-                if (sprite_byte << j) & 0x80 != 0 {
-                    let counter_x = ((self.vx[x as usize] + j) % (self.width)) as usize;
-                    let counter_y = ((self.vx[y as usize] + i) % (self.height)) as usize;
-                    let screen_idx = (counter_y * self.width as usize) + counter_x;
+        if self.mega_enabled() {
+            return self.on_drw_mega(x, y);
+        }
 
-                    if self.screen[screen_idx] {
-                        self.vx[0xF] = 1;
+        // DXY0 draws a 16x16 sprite, two bytes per row
+        let (rows, cols) = if n == 0 && self.platform.supports_superchip() {
+            (16, 16)
+        } else {
+            (n as usize, 8)
+        };
+        let bytes_per_row = cols / 8;
+        let sprite_len = rows * bytes_per_row;
+        // with both XO-CHIP planes selected, the second plane's sprite data
+        // follows the first's
+        let planes = self.selected_planes();
+        self.check_ram(self.ir as usize, sprite_len * planes.clone().count())?;
+
+        let width = self.width as usize;
+        let height = self.height as usize;
+        // the starting position always wraps, only the sprite body is clipped
+        let start_x = self.vx[x as usize] as usize % width;
+        let start_y = self.vx[y as usize] as usize % height;
+
+        let mut collision = false;
+        for (k, plane) in planes.enumerate() {
+            let sprite_addr = self.ir as usize + k * sprite_len;
+
+            for i in 0..rows {
+                let row_addr = sprite_addr + i * bytes_per_row;
+                let sprite_row = if bytes_per_row == 2 {
+                    u16::from_be_bytes([
+                        self.ram[self.resolve(row_addr)],
+                        self.ram[self.resolve(row_addr + 1)],
+                    ])
+                } else {
+                    (self.ram[self.resolve(row_addr)] as u16) << 8
+                };
+
+                for j in 0..cols {
+                    if (sprite_row << j) & 0x8000 != 0 {
+                        let mut counter_x = start_x + j;
+                        let mut counter_y = start_y + i;
+                        if self.quirks.clip_sprites && (counter_x >= width || counter_y >= height) {
+                            continue;
+                        }
+                        counter_x %= width;
+                        counter_y %= height;
+                        let screen_idx = (counter_y * width) + counter_x;
+
+                        let pixels = self.plane_mut(plane);
+                        collision |= pixels[screen_idx];
+                        pixels[screen_idx] ^= true;
                     }
-                    self.screen[screen_idx] ^= true;
                 }
             }
         }
+        self.vx[0xF] = collision as u8;
+
+        // on the real VIP DXYN always waits for the display interrupt
+        self.vblank_wait = self.quirks.display_wait || self.timing.is_some();
 
-        // returning 1 is understood as a call to draw, so the main() loop
-        // knows to request the game window for a redraw
-        Some(1)
+        Ok(StepOutcome::DisplayChanged)
     }
 
-    fn on_skp(&mut self, x: u8) -> Option<u8> {
+    fn on_skp(&mut self, x: u8) -> Result<StepOutcome> {
         // SKP x
         let key = self.vx[x as usize] as usize;
         if self.kp[key] {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_sknp(&mut self, x: u8) -> Option<u8> {
+    fn on_sknp(&mut self, x: u8) -> Result<StepOutcome> {
         // SKNP x
         let key = self.vx[x as usize] as usize;
         if !self.kp[key] {
-            self.pc += 2;
+            self.skip();
         }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_from_dt(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_from_dt(&mut self, x: u8) -> Result<StepOutcome> {
         // LD x, DT
         self.vx[x as usize] = self.dt;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_from_kp(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_from_kp(&mut self, x: u8) -> Result<StepOutcome> {
         // LD x, KP
-        let key_pos = self.kp.iter().position(|&x| x == true);
-        if key_pos.is_some() {
-            self.vx[x as usize] = key_pos.unwrap() as u8;
+        let key_pos = self.kp.iter().position(|&x| x);
+        if let Some(key) = key_pos {
+            self.vx[x as usize] = key as u8;
 
-            None
+            Ok(StepOutcome::Continue)
         } else {
-            // returning 2 is understood as a call to wait, so the main() loop
-            // knows to halt the control flow and wait for another key press
+            // rewind so this instruction runs again once a key is pressed
+            self.pc = self.resolve(self.pc.wrapping_sub(2) as usize) as u16;
 
-            Some(2)
+            Ok(StepOutcome::WaitingForKey)
         }
     }
 
-    fn on_ld_to_dt(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_to_dt(&mut self, x: u8) -> Result<StepOutcome> {
         // LD DT, x
         self.dt = self.vx[x as usize];
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_to_st(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_to_st(&mut self, x: u8) -> Result<StepOutcome> {
         // LD ST, x
+        let was_playing = self.st > 0;
         self.st = self.vx[x as usize];
 
-        None
+        Ok(match (was_playing, self.st > 0) {
+            (false, true) => StepOutcome::SoundStarted,
+            (true, false) => StepOutcome::SoundStopped,
+            _ => StepOutcome::Continue,
+        })
     }
 
-    fn on_add_i(&mut self, x: u8) -> Option<u8> {
+    fn on_add_i(&mut self, x: u8) -> Result<StepOutcome> {
         // ADD I, x
-        self.ir += self.vx[x as usize] as u16;
+        self.ir = self.advance(self.ir, self.vx[x as usize] as usize);
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_f(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_f(&mut self, x: u8) -> Result<StepOutcome> {
         // LD F, x
-        self.ir = (self.vx[x as usize] as u16) * 5;
+        self.ir = self.font_addr + ((self.vx[x as usize] & 0xF) as u16) * 5;
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_b(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_b(&mut self, x: u8) -> Result<StepOutcome> {
         // LD B, x
         let start_addr = self.ir as usize;
-        self.ram[start_addr] = self.vx[x as usize] / 100;
-        self.ram[start_addr + 1] = (self.vx[x as usize] % 100) / 10;
-        self.ram[start_addr + 2] = self.vx[x as usize] % 10;
+        self.check_ram(start_addr, 3)?;
+        let value = self.vx[x as usize];
+        for (i, digit) in [value / 100, (value % 100) / 10, value % 10].into_iter().enumerate() {
+            let addr = self.resolve(start_addr + i);
+            self.ram[addr] = digit;
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_to_i(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_to_i(&mut self, x: u8) -> Result<StepOutcome> {
         // LD [I], x
         let start_addr = self.ir as usize;
-        self.ram[start_addr..=(start_addr + (x as usize))]
-            .copy_from_slice(&self.vx[0..=(x as usize)]);
+        self.check_ram(start_addr, x as usize + 1)?;
+        for i in 0..=(x as usize) {
+            let addr = self.resolve(start_addr + i);
+            self.ram[addr] = self.vx[i];
+        }
+        if self.quirks.load_store_increments_i {
+            self.ir = self.advance(self.ir, x as usize + 1);
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
 
-    fn on_ld_from_i(&mut self, x: u8) -> Option<u8> {
+    fn on_ld_from_i(&mut self, x: u8) -> Result<StepOutcome> {
         // LD x, [I]
         let start_addr = self.ir as usize;
-        self.vx[0..=(x as usize)]
-            .copy_from_slice(&self.ram[start_addr..=(start_addr + (x as usize))]);
+        self.check_ram(start_addr, x as usize + 1)?;
+        for i in 0..=(x as usize) {
+            self.vx[i] = self.ram[self.resolve(start_addr + i)];
+        }
+        if self.quirks.load_store_increments_i {
+            self.ir = self.advance(self.ir, x as usize + 1);
+        }
 
-        None
+        Ok(StepOutcome::Continue)
     }
-}
 
+    fn on_scroll(&mut self, dx: isize, dy: isize) -> Result<StepOutcome> {
+        // SCD n / SCU n / SCR / SCL
+        if let Some(mega) = self.mega.as_deref_mut().filter(|mega| mega.enabled) {
+            mega.scroll(dx, dy);
+            return Ok(StepOutcome::DisplayChanged);
+        }
 
-#[cfg(test)]
-mod tests {
+        let width = self.width as isize;
+        let height = self.height as isize;
+
+        for plane in self.selected_planes() {
+            let pixels = self.plane_mut(plane);
+            let src = *pixels;
+
+            for row in 0..height {
+                for col in 0..width {
+                    let (src_row, src_col) = (row - dy, col - dx);
+                    let in_bounds = (0..height).contains(&src_row) && (0..width).contains(&src_col);
+                    pixels[(row * width + col) as usize] =
+                        in_bounds && src[(src_row * width + src_col) as usize];
+                }
+            }
+        }
+
+        Ok(StepOutcome::DisplayChanged)
+    }
+
+    fn on_plane(&mut self, n: u8) -> Result<StepOutcome> {
+        // PLANE n
+        self.planes = n & 0x3;
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_long(&mut self) -> Result<StepOutcome> {
+        // LD I, long
+        // the address is the word following the instruction
+        self.ir = self.fetch()?;
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_to_i_range(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
+        // LD [I], x-y
+        // the registers are stored in order from x to y, even when x > y
+        let len = x.abs_diff(y) as usize + 1;
+        let start_addr = self.ir as usize;
+        self.check_ram(start_addr, len)?;
+
+        for i in 0..len {
+            let reg = if x <= y { x as usize + i } else { x as usize - i };
+            let addr = self.resolve(start_addr + i);
+            self.ram[addr] = self.vx[reg];
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_from_i_range(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
+        // LD x-y, [I]
+        let len = x.abs_diff(y) as usize + 1;
+        let start_addr = self.ir as usize;
+        self.check_ram(start_addr, len)?;
+
+        for i in 0..len {
+            let reg = if x <= y { x as usize + i } else { x as usize - i };
+            self.vx[reg] = self.ram[self.resolve(start_addr + i)];
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    // indices (0 or 1) of the planes currently selected by `planes`
+    fn selected_planes(&self) -> impl Iterator<Item = usize> + Clone + use<R, MEMORY> {
+        let planes = self.planes;
+        (0..2).filter(move |plane| planes & (1 << plane) != 0)
+    }
+
+    fn plane_mut(&mut self, plane: usize) -> &mut Plane {
+        if plane == 0 {
+            &mut self.screen
+        } else {
+            &mut self.plane2
+        }
+    }
+
+    // skips the next instruction, which is four bytes long if it is an
+    // XO-CHIP long load
+    fn skip(&mut self) {
+        let addr = self.pc as usize;
+        let long = self.platform.supports_xochip()
+            && self.check_ram(addr, 2).is_ok()
+            && self.ram[self.resolve(addr)] == 0xF0
+            && self.ram[self.resolve(addr + 1)] == 0x00;
+
+        self.pc = self.advance(self.pc, if long { 4 } else { 2 });
+    }
+
+    fn on_resolution(&mut self, hires: bool) -> Result<StepOutcome> {
+        // LOW / HIGH
+        let (width, height) = self.platform.resolution();
+        (self.width, self.height) = if hires {
+            (width * 2, height * 2)
+        } else {
+            (width, height)
+        };
+        self.hires = hires;
+        self.screen.fill(false);
+        self.plane2.fill(false);
+
+        Ok(StepOutcome::ResolutionChanged)
+    }
+
+    fn on_ld_hf(&mut self, x: u8) -> Result<StepOutcome> {
+        // LD HF, x
+        self.ir = self.big_font_addr + ((self.vx[x as usize] & 0xF) as u16) * 10;
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_to_r(&mut self, x: u8) -> Result<StepOutcome> {
+        // LD R, x
+        self.rpl[0..=(x as usize)].copy_from_slice(&self.vx[0..=(x as usize)]);
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_from_r(&mut self, x: u8) -> Result<StepOutcome> {
+        // LD x, R
+        self.vx[0..=(x as usize)].copy_from_slice(&self.rpl[0..=(x as usize)]);
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_audio(&mut self) -> Result<StepOutcome> {
+        // AUDIO
+        let start = self.ir as usize;
+        self.check_ram(start, 16)?;
+        for i in 0..16 {
+            self.pattern[i] = self.ram[self.resolve(start + i)];
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_pitch(&mut self, x: u8) -> Result<StepOutcome> {
+        // PITCH x
+        self.pitch = self.vx[x as usize];
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_bgc(&mut self) -> Result<StepOutcome> {
+        // BGC
+        self.background = (self.background + 1) % 4;
+
+        Ok(StepOutcome::DisplayChanged)
+    }
+
+    fn on_col(&mut self, x: u8, y: u8, n: u8) -> Result<StepOutcome> {
+        // COL x, y, n
+        // vx picks the columns of 8 pixels: the low nibble is the first,
+        // the high nibble how many more follow. with n = 0, vy does the same
+        // for rows of 4 pixels, otherwise n single rows start at row vy
+        let vx = self.vx[x as usize] as usize;
+        let vy = self.vx[y as usize] as usize;
+        let colour = self.vx[(x as usize + 1) % 16] & 0x7;
+
+        let columns = (vx & 0xF)..=((vx & 0xF) + (vx >> 4));
+        let rows = if n == 0 {
+            ((vy & 0xF) * 4)..((vy & 0xF) + (vy >> 4) + 1) * 4
+        } else {
+            vy..(vy + n as usize)
+        };
+
+        for row in rows.filter(|&row| row < COLOUR_ROWS) {
+            for col in columns.clone().filter(|&col| col < COLOUR_COLUMNS) {
+                self.colours[row * COLOUR_COLUMNS + col] = colour;
+            }
+        }
+
+        Ok(StepOutcome::DisplayChanged)
+    }
+
+    fn on_skp2(&mut self, x: u8) -> Result<StepOutcome> {
+        // SKP2 x
+        let key = self.vx[x as usize] as usize & 0xF;
+        if self.kp2[key] {
+            self.skip();
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_sknp2(&mut self, x: u8) -> Result<StepOutcome> {
+        // SKNP2 x
+        let key = self.vx[x as usize] as usize & 0xF;
+        if !self.kp2[key] {
+            self.skip();
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_mega(&mut self, enabled: bool) -> Result<StepOutcome> {
+        // MEGAOFF / MEGAON
+        let mega = self.mega_mut()?;
+        mega.enabled = enabled;
+        mega.clear();
+        (self.width, self.height) = if enabled {
+            (megachip::WIDTH as u16, megachip::HEIGHT as u16)
+        } else {
+            self.platform.resolution()
+        };
+        self.hires = false;
+        self.screen.fill(false);
+        self.plane2.fill(false);
+
+        Ok(StepOutcome::ResolutionChanged)
+    }
+
+    fn on_ld_hi(&mut self, nn: u8) -> Result<StepOutcome> {
+        // LDHI I, nn, long
+        // a 24-bit address, the low 16 bits are the word following the
+        // instruction
+        let addr = ((nn as usize) << 16) | self.fetch()? as usize;
+        if addr >= self.memory_size() {
+            return Err(Error::MemoryOutOfBounds { addr });
+        }
+        self.ir = addr as u16;
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ld_pal(&mut self, nn: u8) -> Result<StepOutcome> {
+        // LDPAL nn
+        // nn argb colours from I, into palette entries 1 to nn
+        let start = self.ir as usize;
+        let count = nn as usize;
+        self.check_ram(start, count * 4)?;
+
+        for i in 0..count {
+            let [a, r, g, b] = [0, 1, 2, 3].map(|j| self.ram[self.resolve(start + i * 4 + j)]);
+            self.mega_mut()?.palette[i + 1] = [r, g, b, a];
+        }
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_sprw(&mut self, nn: u8) -> Result<StepOutcome> {
+        // SPRW nn
+        self.mega_mut()?.sprite_width = if nn == 0 { 256 } else { nn as usize };
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_sprh(&mut self, nn: u8) -> Result<StepOutcome> {
+        // SPRH nn
+        self.mega_mut()?.sprite_height = if nn == 0 { 256 } else { nn as usize };
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_alpha(&mut self, nn: u8) -> Result<StepOutcome> {
+        // ALPHA nn
+        self.mega_mut()?.alpha = nn;
+
+        Ok(StepOutcome::DisplayChanged)
+    }
+
+    fn on_digisnd(&mut self, n: u8) -> Result<StepOutcome> {
+        // DIGISND n
+        // I points at a 16-bit sample rate, a 24-bit length and a reserved
+        // byte, followed by the samples. n = 0 loops them
+        // the samples are played straight out of ram, so they have to fit
+        // in memory whatever the memory policy
+        let header = self.ir as usize;
+        self.check_contiguous(header, 6)?;
+        let rate = u16::from_be_bytes([self.ram[header], self.ram[header + 1]]);
+        let len = ((self.ram[header + 2] as usize) << 16)
+            | ((self.ram[header + 3] as usize) << 8)
+            | self.ram[header + 4] as usize;
+        self.check_contiguous(header + 6, len)?;
+
+        self.mega_mut()?.start_sample(header + 6, len, rate, n == 0);
+
+        Ok(StepOutcome::SoundStarted)
+    }
+
+    fn on_stopsnd(&mut self) -> Result<StepOutcome> {
+        // STOPSND
+        self.mega_mut()?.sample = None;
+
+        Ok(StepOutcome::SoundStopped)
+    }
+
+    fn on_bmode(&mut self, n: u8) -> Result<StepOutcome> {
+        // BMODE n
+        self.mega_mut()?.blend = BlendMode::from_n(n);
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_ccol(&mut self, nn: u8) -> Result<StepOutcome> {
+        // CCOL nn
+        self.mega_mut()?.collision_index = nn;
+
+        Ok(StepOutcome::Continue)
+    }
+
+    fn on_drw_mega(&mut self, x: u8, y: u8) -> Result<StepOutcome> {
+        // DRW x, y, n in the 256x192 mode
+        // the sprite is sprite_width * sprite_height palette indices, index 0
+        // is transparent. VF is set when a pixel lands on the collision index
+        let mega = self.mega_mut()?;
+        let (cols, rows) = (mega.sprite_width, mega.sprite_height);
+        let sprite_addr = self.ir as usize;
+        self.check_ram(sprite_addr, cols * rows)?;
+
+        let start_x = self.vx[x as usize] as usize;
+        let start_y = self.vx[y as usize] as usize;
+
+        let mut collision = false;
+        for i in 0..rows {
+            for j in 0..cols {
+                let index = self.ram[self.resolve(sprite_addr + i * cols + j)];
+                let (px, py) = (start_x + j, start_y + i);
+                if index == 0 || px >= megachip::WIDTH || py >= megachip::HEIGHT {
+                    continue;
+                }
+
+                let idx = py * megachip::WIDTH + px;
+                let mega = self.mega_mut()?;
+                collision |= mega.indices[idx] == mega.collision_index;
+                mega.indices[idx] = index;
+                let colour = mega.palette[index as usize];
+                mega.framebuffer[idx] = mega.blend.blend(mega.framebuffer[idx], colour);
+            }
+        }
+        self.vx[0xF] = collision as u8;
+
+        Ok(StepOutcome::DisplayChanged)
+    }
+
+    // under the Fault policy, fails unless `len` bytes from `start_addr` are
+    // all in memory. the other policies can always resolve an address
+    fn check_ram(&self, start_addr: usize, len: usize) -> Result<()> {
+        if self.memory_policy == MemoryPolicy::Fault {
+            self.check_contiguous(start_addr, len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_contiguous(&self, start_addr: usize, len: usize) -> Result<()> {
+        if start_addr + len > self.memory_size() {
+            Err(Error::MemoryOutOfBounds {
+                addr: start_addr.max(self.memory_size()),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::font;
+    use crate::random::{Generator, RandomKind};
 
     #[test]
     fn test_fonts() {
@@ -715,6 +2183,47 @@ mod tests {
         assert_eq!(memory.ram[0x050..0x0A0], exp_result);
     }
 
+    #[test]
+    fn test_encode_round_trip() {
+        for platform in Platform::ALL {
+            for opcode in 0..=0xFFFF {
+                let Some(ins) = Instruction::decode_for(opcode, platform) else {
+                    continue;
+                };
+
+                // HiRes clears the screen with either opcode
+                if platform.supports_two_page() && opcode == 0x0230 {
+                    assert_eq!(ins.encode_for(platform), 0x00E0);
+                } else {
+                    assert_eq!(ins.encode_for(platform), opcode, "{} on {}", ins, platform);
+                }
+                assert_eq!(ins.to_string().parse::<Instruction>(), Ok(ins), "{}", ins);
+            }
+        }
+        assert_eq!(Instruction::SCU(0x3).encode(), 0x00D3);
+    }
+
+    #[test]
+    fn test_parse_instruction() {
+        let cases = [
+            ("cls", Instruction::CLS),
+            ("LD VA, #12", Instruction::LD(0xA, LDType::Byte(0x12))),
+            ("LD V1, 200", Instruction::LD(0x1, LDType::Byte(200))),
+            ("ADD I, V3", Instruction::ADD(0x3, AddType::I)),
+            ("JP V0, 0x300", Instruction::JP(JPType::FromV0(0x300))),
+            ("SHR V1", Instruction::SHR(0x1, 0x1)),
+            ("ld [i], v0x2-v0x5", Instruction::LD(0x2, LDType::ToIRange(0x5))),
+            ("DRW V0, V1, 5", Instruction::DRW(0x0, 0x1, 0x5)),
+        ];
+        for (text, ins) in cases {
+            assert_eq!(text.parse::<Instruction>(), Ok(ins), "{}", text);
+        }
+
+        for text in ["", "NOP", "LD V16, 1", "JP 0x1000", "DRW V0, V1, 16", "CLS V0"] {
+            assert_eq!(text.parse::<Instruction>(), Err(InvalidInstruction), "{}", text);
+        }
+    }
+
     #[test]
     fn test_display() {
         let mut display = Cpu::init();
@@ -740,33 +2249,33 @@ mod tests {
     fn test_00e0() {
         // CLS
         let mut cpu = Cpu::init();
-        cpu.screen = vec![true; 64 * 32];
+        cpu.screen = [true; MAX_WIDTH * MAX_HEIGHT];
 
         cpu.ram[0x200] = 0x00;
         cpu.ram[0x201] = 0xe0;
 
-        let opcode = cpu.fetch();
+        let opcode = cpu.fetch().unwrap();
         let ins = Instruction::decode(opcode).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
-        assert_eq!(cpu.screen, vec![false; 64 * 32]);
+        assert_eq!(cpu.screen, [false; MAX_WIDTH * MAX_HEIGHT]);
     }
 
     #[test]
     fn test_00ee() {
         // RET
-        for stack_idx in 1..=15 {
+        for stack_idx in 1..=16 {
             let mut cpu = Cpu::init();
             let ret_addr: u16 = 0x250;
             cpu.sp = stack_idx;
-            cpu.stack[cpu.sp as usize] = ret_addr;
+            cpu.stack[cpu.sp - 1] = ret_addr;
 
             cpu.ram[0x200] = 0x00;
             cpu.ram[0x201] = 0xee;
 
-            let opcode = cpu.fetch();
+            let opcode = cpu.fetch().unwrap();
             let ins = Instruction::decode(opcode).unwrap();
-            let _ = cpu.execute(ins);
+            cpu.execute(ins).unwrap();
 
             assert_eq!(cpu.pc, ret_addr);
             assert_eq!(cpu.sp, stack_idx - 1);
@@ -783,9 +2292,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let opcode = cpu.fetch();
+        let opcode = cpu.fetch().unwrap();
         let ins = Instruction::decode(opcode).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, jp_addr);
     }
@@ -793,7 +2302,7 @@ mod tests {
     #[test]
     fn test_2nnn() {
         // CALL addr
-        for stack_idx in 0..=14 {
+        for stack_idx in 0..=15 {
             let mut cpu = Cpu::init();
             let sr_addr: u16 = 0x250;
             cpu.sp = stack_idx;
@@ -802,11 +2311,11 @@ mod tests {
             cpu.ram[0x200] = (tmp >> 8) as u8;
             cpu.ram[0x201] = tmp as u8;
 
-            let opcode = cpu.fetch();
+            let opcode = cpu.fetch().unwrap();
             let ins = Instruction::decode(opcode).unwrap();
-            let _ = cpu.execute(ins);
+            cpu.execute(ins).unwrap();
 
-            assert_eq!(cpu.stack[cpu.sp as usize], 0x202);
+            assert_eq!(cpu.stack[stack_idx], 0x202);
             assert_eq!(cpu.pc, 0x250);
             assert_eq!(cpu.sp, stack_idx + 1);
         }
@@ -824,9 +2333,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -843,9 +2352,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -864,9 +2373,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -882,9 +2391,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], kk);
     }
@@ -902,9 +2411,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], orig + kk);
     }
@@ -921,9 +2430,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], cpu.vx[y as usize]);
     }
@@ -943,9 +2452,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], x_val | y_val);
     }
@@ -965,9 +2474,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], x_val & y_val);
     }
@@ -987,9 +2496,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], x_val ^ y_val);
     }
@@ -1010,9 +2519,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], sum as u8);
         assert_eq!(cpu.vx[0xF] == 1, (0xFF00 & sum) > 1);
@@ -1028,22 +2537,18 @@ mod tests {
         let y_val: u8 = 0xC0;
         cpu.vx[x as usize] = x_val;
         cpu.vx[y as usize] = y_val;
-        let diff: u16 = if x_val > y_val {
-            (x_val as u16) - (y_val as u16)
-        } else {
-            (y_val as u16) - (x_val as u16)
-        };
+        let diff = x_val.wrapping_sub(y_val);
 
         let tmp = 0x8000 | ((x as u16) << 8) | ((y as u16) << 4) | 0x5;
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
-        assert_eq!(cpu.vx[x as usize], diff as u8);
-        assert_eq!(cpu.vx[0xF] == 1, x_val > y_val);
+        assert_eq!(cpu.vx[x as usize], diff);
+        assert_eq!(cpu.vx[0xF] == 1, x_val >= y_val);
     }
 
     #[test]
@@ -1059,12 +2564,12 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], x_val >> 1);
-        assert_eq!(cpu.vx[0xF] == 1, x_val.trailing_ones() > 1);
+        assert_eq!(cpu.vx[0xF], x_val & 0x1);
     }
 
     #[test]
@@ -1077,22 +2582,18 @@ mod tests {
         let y_val: u8 = 0x01;
         cpu.vx[x as usize] = x_val;
         cpu.vx[y as usize] = y_val;
-        let diff: u16 = if y_val > x_val {
-            (y_val as u16) - (x_val as u16)
-        } else {
-            (x_val as u16) - (y_val as u16)
-        };
+        let diff = y_val.wrapping_sub(x_val);
 
         let tmp = 0x8000 | ((x as u16) << 8) | ((y as u16) << 4) | 0x7;
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
-        assert_eq!(cpu.vx[x as usize], diff as u8);
-        assert_eq!(cpu.vx[0xF] == 1, y_val > x_val);
+        assert_eq!(cpu.vx[x as usize], diff);
+        assert_eq!(cpu.vx[0xF] == 1, y_val >= x_val);
     }
 
     #[test]
@@ -1108,12 +2609,46 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], x_val << 1);
-        assert_eq!(cpu.vx[0xF] == 1, x_val.leading_ones() > 1);
+        assert_eq!(cpu.vx[0xF], x_val >> 7);
+    }
+
+    #[test]
+    fn test_8xyn_flags() {
+        // VF is the carry for ADD, set when nothing is borrowed for SUB and
+        // SUBN (equal values borrow nothing), and the bit shifted out for SHR
+        // and SHL. it is written after the result
+        for (opcode, x_val, y_val, result, flag) in [
+            (0x8124, 0x01, 0x01, 0x02, 0),
+            (0x8124, 0xFF, 0x01, 0x00, 1),
+            (0x8125, 0x05, 0x05, 0x00, 1),
+            (0x8125, 0x05, 0x06, 0xFF, 0),
+            (0x8127, 0x05, 0x05, 0x00, 1),
+            (0x8127, 0x06, 0x05, 0xFF, 0),
+            (0x8126, 0x03, 0x00, 0x01, 1),
+            (0x812E, 0x81, 0x00, 0x02, 1),
+        ] {
+            let mut cpu = Cpu::init();
+            cpu.vx[0x1] = x_val;
+            cpu.vx[0x2] = y_val;
+            cpu.vx[0xF] = 1 - flag;
+
+            cpu.execute(Instruction::decode(opcode).unwrap()).unwrap();
+
+            assert_eq!(cpu.vx[0x1], result, "{:#06x}", opcode);
+            assert_eq!(cpu.vx[0xF], flag, "{:#06x}", opcode);
+        }
+
+        // with VF as the destination the flag wins over the result
+        let mut cpu = Cpu::init();
+        cpu.vx[0xF] = 0xFF;
+        cpu.vx[0x1] = 0x01;
+        cpu.execute(Instruction::decode(0x8F14).unwrap()).unwrap();
+        assert_eq!(cpu.vx[0xF], 1);
     }
 
     #[test]
@@ -1130,9 +2665,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -1147,9 +2682,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.ir, addr);
     }
@@ -1165,19 +2700,37 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, (cpu.vx[0x0] as u16) + addr);
     }
 
+    #[test]
+    fn test_seeded_runs_match() {
+        // RND V0, 0xFF then LD [I], V0 into a buffer, 32 times
+        let run = |kind| {
+            let mut cpu = Cpu::with_platform(Platform::CosmacVip, Generator::new(kind, Some(7)));
+            cpu.ir = 0x300;
+            for _ in 0..32 {
+                run_opcode(&mut cpu, 0xC0FF);
+                run_opcode(&mut cpu, 0xF055);
+            }
+            cpu.ram[0x300..0x320].to_vec()
+        };
+
+        for kind in [RandomKind::XorShift, RandomKind::Vip] {
+            assert_eq!(run(kind), run(kind));
+        }
+    }
+
     #[test]
     fn test_cxkk() {
         // RND x, kk
-        let mut cpu = Cpu::init();
-        let x: u8 = 0xF;
-        let kk: u8 = 0xFF;
+        let mut cpu = Cpu::with_rng(|| 0xA5);
+        let x: u8 = 0xE;
+        let kk: u8 = 0x0F;
         let val: u8 = 0xF;
         cpu.vx[x as usize] = val;
 
@@ -1185,11 +2738,11 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
-        assert_eq!(cpu.vx[x as usize], 0);
+        assert_eq!(cpu.vx[x as usize], 0xA5 & kk);
     }
 
     #[test]
@@ -1211,12 +2764,12 @@ mod tests {
         }
         cpu.screen[0x20C] = true;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[0xF], 1);
-        assert_eq!(cpu.screen[0x20C], false);
+        assert!(!cpu.screen[0x20C]);
     }
 
     #[test]
@@ -1232,9 +2785,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -1252,9 +2805,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.pc, 0x204);
     }
@@ -1270,9 +2823,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], cpu.dt);
     }
@@ -1289,9 +2842,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], key);
     }
@@ -1307,9 +2860,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], cpu.dt);
     }
@@ -1325,9 +2878,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.vx[x as usize], cpu.st);
     }
@@ -1345,9 +2898,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.ir, (cpu.vx[x as usize] as u16) + initial_ir);
     }
@@ -1363,11 +2916,12 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
-        assert_eq!(cpu.ir, (cpu.vx[x as usize] as u16) * 5);
+        // only the low nibble picks the digit
+        assert_eq!(cpu.ir, 0x050 + 5 * 5);
     }
 
     #[test]
@@ -1382,9 +2936,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(cpu.ram[cpu.ir as usize], 1);
         assert_eq!(cpu.ram[(cpu.ir + 1) as usize], 3);
@@ -1404,9 +2958,9 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(
             cpu.ram[(cpu.ir as usize)..=((cpu.ir + (x as u16)) as usize)],
@@ -1428,13 +2982,1607 @@ mod tests {
         cpu.ram[0x200] = (tmp >> 8) as u8;
         cpu.ram[0x201] = tmp as u8;
 
-        let instruction = cpu.fetch();
+        let instruction = cpu.fetch().unwrap();
         let ins = Instruction::decode(instruction).unwrap();
-        let _ = cpu.execute(ins);
+        cpu.execute(ins).unwrap();
 
         assert_eq!(
             cpu.ram[(cpu.ir as usize)..=((cpu.ir + (x as u16)) as usize)],
             cpu.vx[0..=(x as usize)]
         );
     }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_rom_from_path() {
+        let mut cpu = Cpu::init();
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/tests/ibm_logo.ch8");
+        cpu.load_rom_from_path(path).unwrap();
+
+        assert_eq!(cpu.ram[0x200..0x202], [0x00, 0xE0]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_rom_missing() {
+        let mut cpu = Cpu::init();
+        let result = cpu.load_rom_from_path("roms/tests/does_not_exist.ch8");
+
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_load_rom_from_bytes() {
+        let mut cpu = Cpu::init();
+        let rom = include_bytes!("../roms/tests/ibm_logo.ch8");
+        cpu.load_rom_from_bytes(rom).unwrap();
+
+        assert_eq!(cpu.ram[0x200..(0x200 + rom.len())], rom[..]);
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_load_rom_from_reader() {
+        let mut cpu = Cpu::init();
+        cpu.load_addr = 0x600;
+        let rom: &[u8] = &[0x00, 0xE0, 0x16, 0x00];
+        cpu.load_rom_from_reader(rom).unwrap();
+
+        assert_eq!(cpu.ram[0x600..0x604], [0x00, 0xE0, 0x16, 0x00]);
+        assert_eq!(cpu.pc, 0x600);
+    }
+
+    #[test]
+    fn test_load_rom_too_large() {
+        let mut cpu = Cpu::init();
+        let rom = vec![0xFF; 0xE01];
+        let result = cpu.load_rom_from_bytes(&rom);
+
+        assert!(matches!(
+            result,
+            Err(Error::RomTooLarge {
+                size: 0xE01,
+                max: 0xE00
+            })
+        ));
+
+        let rom = vec![0xFF; 0xE00];
+        assert!(cpu.load_rom_from_bytes(&rom).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_print_ram_bounds() {
+        // the last word of a 64K platform, and nothing to stop at
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.load_addr = 0xFFFE;
+        cpu.ram[0xFFFE..0x10000].copy_from_slice(&[0x00, 0xE0]);
+        cpu.print_ram();
+
+        let mut cpu = Cpu::for_platform(Platform::Chip8X);
+        cpu.ram[0x300..0x302].copy_from_slice(&[0x00, 0xE0]);
+        cpu.print_ram();
+        assert_eq!(cpu.pc, 0x300);
+    }
+
+    #[test]
+    fn test_load_addr_out_of_range() {
+        let mut cpu = Cpu::init();
+
+        // an empty rom still needs somewhere to start
+        cpu.load_addr = 0xFFF;
+        cpu.load_rom_from_bytes(&[]).unwrap();
+        assert_eq!(cpu.pc, 0xFFF);
+
+        cpu.load_addr = 0x1000;
+        assert!(matches!(
+            cpu.load_rom_from_bytes(&[]),
+            Err(Error::MemoryOutOfBounds { addr: 0x1000 })
+        ));
+        cpu.load_addr = 0xFFFF;
+        assert!(matches!(
+            cpu.load_rom_from_bytes(&[0x00, 0xE0]),
+            Err(Error::MemoryOutOfBounds { addr: 0xFFFF })
+        ));
+        assert_eq!(cpu.pc, 0xFFF);
+
+        // a HI-RES rom enters 0xC0 bytes in, which has to be in memory too
+        let mut cpu = Cpu::for_platform(Platform::HiRes);
+        cpu.load_addr = 0xF80;
+        assert!(matches!(
+            cpu.load_rom_from_bytes(&[0x12, 0x60]),
+            Err(Error::MemoryOutOfBounds { addr: 0x1040 })
+        ));
+
+        // past the ram of a smaller cpu, even with a platform swapped in later
+        let mut cpu: Cpu<XorShift, 0x1000> =
+            Cpu::with_memory(Platform::Modern, XorShift::default());
+        cpu.platform = Platform::XoChip;
+        cpu.load_addr = 0x2000;
+        assert!(matches!(
+            cpu.load_rom_from_bytes(&[]),
+            Err(Error::MemoryOutOfBounds { addr: 0x2000 })
+        ));
+    }
+
+    #[test]
+    fn test_illegal_opcode() {
+        let mut cpu = Cpu::init();
+        cpu.ram[0x200] = 0x5A;
+        cpu.ram[0x201] = 0xB1;
+
+        let result = cpu.step();
+
+        assert!(matches!(
+            result,
+            Err(Error::IllegalOpcode {
+                addr: 0x200,
+                opcode: 0x5AB1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_raw0() {
+        let mut cpu = Cpu::init();
+
+        let result = cpu.step();
+
+        assert!(matches!(
+            result,
+            Err(Error::IllegalOpcode {
+                addr: 0x200,
+                opcode: 0x0000,
+                ..
+            })
+        ));
+        assert!(matches!(cpu.execute(Instruction::RAW0), Ok(StepOutcome::Halted)));
+    }
+
+    // LD V0, 0x01 / ADD V0, 0x02 / an illegal 5XY1 / LD V1, 0x03
+    const ILLEGAL_ROM: [u8; 8] = [0x60, 0x01, 0x70, 0x02, 0x5A, 0xB1, 0x61, 0x03];
+
+    #[test]
+    fn test_illegal_opcode_halt() {
+        let mut cpu = Cpu::init();
+        cpu.load_rom_from_bytes(&ILLEGAL_ROM).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let Err(Error::IllegalOpcode { addr, opcode, history }) = cpu.step() else {
+            panic!("expected an illegal opcode");
+        };
+
+        assert_eq!((addr, opcode), (0x204, 0x5AB1));
+        assert_eq!(history.iter().collect::<Vec<_>>(), [(0x200, 0x6001), (0x202, 0x7002)]);
+        let report = Error::IllegalOpcode { addr, opcode, history }.to_string();
+        assert!(report.starts_with("illegal opcode 0x5ab1 at 0x204"));
+        assert!(report.contains("0x202  0x7002"));
+    }
+
+    #[test]
+    fn test_illegal_opcode_skip() {
+        let mut cpu = Cpu::init();
+        cpu.opcode_policy = OpcodePolicy::Skip;
+        cpu.load_rom_from_bytes(&ILLEGAL_ROM).unwrap();
+
+        for _ in 0..4 {
+            assert_eq!(cpu.step().unwrap(), StepOutcome::Continue);
+        }
+
+        assert_eq!(cpu.vx[0x0..0x2], [3, 3]);
+        assert_eq!(cpu.pc, 0x208);
+    }
+
+    #[test]
+    fn test_illegal_opcode_trap() {
+        let mut cpu = Cpu::init();
+        cpu.opcode_policy = OpcodePolicy::Trap;
+        cpu.load_rom_from_bytes(&ILLEGAL_ROM).unwrap();
+
+        let frame = cpu.run_frame(10).unwrap();
+
+        assert_eq!(frame.trapped, Some(0x5AB1));
+        assert_eq!(frame.instructions, 3);
+        assert_eq!(cpu.pc, 0x204);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Trapped(0x5AB1));
+    }
+
+    #[test]
+    fn test_history_decodes_for_the_platform() {
+        // LD V0, 0x01 / HIGH / an illegal 5XY1
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.load_rom_from_bytes(&[0x60, 0x01, 0x00, 0xFF, 0x5A, 0xB1]).unwrap();
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let report = cpu.step().unwrap_err().to_string();
+
+        assert!(report.contains("0x202  0x00ff  HIGH"));
+        assert!(!report.contains("SYS"));
+    }
+
+    #[test]
+    fn test_history_keeps_the_latest() {
+        let mut history = History::default();
+        for i in 0..(HISTORY_LEN as u16 + 3) {
+            history.push(0x200 + 2 * i, i);
+        }
+
+        let entries: Vec<_> = history.iter().collect();
+        assert_eq!(entries.len(), HISTORY_LEN);
+        assert_eq!(entries[0], (0x206, 3));
+        assert_eq!(entries[HISTORY_LEN - 1], (0x200 + 2 * (HISTORY_LEN as u16 + 2), 10));
+    }
+
+    #[test]
+    fn test_fetch_out_of_bounds() {
+        let mut cpu = Cpu::init();
+        cpu.pc = 0xFFF;
+
+        let result = cpu.fetch();
+
+        assert!(matches!(
+            result,
+            Err(Error::MemoryOutOfBounds { addr: 0xFFF })
+        ));
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.sp = 16;
+
+        cpu.ram[0x200] = 0x22;
+        cpu.ram[0x201] = 0x50;
+
+        let result = cpu.step();
+
+        assert!(matches!(result, Err(Error::StackOverflow { addr: 0x200 })));
+    }
+
+    #[test]
+    fn test_stack_depth() {
+        for (platform, depth) in [(Platform::CosmacVip, 12), (Platform::SuperChip, 16)] {
+            let mut cpu = Cpu::for_platform(platform);
+            // CALL 0x200, forever
+            cpu.ram[0x200..0x202].copy_from_slice(&[0x22, 0x00]);
+
+            for _ in 0..depth {
+                cpu.step().unwrap();
+            }
+            assert_eq!(cpu.sp, depth);
+            assert!(matches!(cpu.step(), Err(Error::StackOverflow { addr: 0x200 })));
+        }
+    }
+
+    #[test]
+    fn test_modern_stack_depth() {
+        let mut cpu = Cpu::for_platform(Platform::Modern);
+        // CALL 0x200, forever
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x22, 0x00]);
+
+        // the stack keeps growing, and every return address is kept
+        #[cfg(feature = "std")]
+        {
+            for _ in 0..1000 {
+                cpu.step().unwrap();
+            }
+            assert_eq!(cpu.sp, 1000);
+            cpu.ram[0x300..0x302].copy_from_slice(&[0x00, 0xEE]);
+            for _ in 0..1000 {
+                cpu.pc = 0x300;
+                cpu.step().unwrap();
+                assert_eq!(cpu.pc, 0x202);
+            }
+            assert_eq!(cpu.sp, 0);
+        }
+
+        // without std it runs out of array
+        #[cfg(not(feature = "std"))]
+        {
+            for _ in 0..MAX_STACK_DEPTH {
+                cpu.step().unwrap();
+            }
+            assert!(matches!(cpu.step(), Err(Error::StackOverflow { addr: 0x200 })));
+        }
+    }
+
+    #[test]
+    fn test_memory_sized_cpu() {
+        // a 4K cpu runs the 4K platforms
+        let mut cpu: Cpu<XorShift, 0x1000> =
+            Cpu::with_memory(Platform::SuperChip, XorShift::default());
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x6A, 0x42]);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.vx[0xA], 0x42);
+        assert_eq!(cpu.memory_size(), cpu.ram.len());
+    }
+
+    #[test]
+    #[should_panic(expected = "needs 0x10000 bytes of ram")]
+    fn test_memory_sized_cpu_too_small() {
+        let _: Cpu<XorShift, 0x1000> = Cpu::with_memory(Platform::XoChip, XorShift::default());
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut cpu = Cpu::init();
+
+        cpu.ram[0x200] = 0x00;
+        cpu.ram[0x201] = 0xEE;
+
+        let result = cpu.step();
+
+        assert!(matches!(result, Err(Error::StackUnderflow { addr: 0x200 })));
+    }
+
+    #[test]
+    fn test_ld_from_i_out_of_bounds() {
+        let mut cpu = Cpu::init();
+        cpu.ir = 0xFFE;
+
+        cpu.ram[0x200] = 0xF5;
+        cpu.ram[0x201] = 0x65;
+
+        let result = cpu.step();
+
+        assert!(matches!(
+            result,
+            Err(Error::MemoryOutOfBounds { addr: 0x1000 })
+        ));
+    }
+
+    fn cpu_with_policy(policy: MemoryPolicy) -> Cpu {
+        let mut cpu = Cpu::init();
+        cpu.memory_policy = policy;
+        cpu
+    }
+
+    #[test]
+    fn test_memory_policy_fetch() {
+        for (policy, expected, pc) in [
+            (MemoryPolicy::Wrap, 0x1234, 0x001),
+            (MemoryPolicy::Clamp, 0x1212, 0xFFF),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.pc = 0xFFF;
+            cpu.ram[0xFFF] = 0x12;
+            cpu.ram[0x000] = 0x34;
+
+            assert_eq!(cpu.fetch().unwrap(), expected);
+            assert_eq!(cpu.pc, pc);
+        }
+    }
+
+    #[test]
+    fn test_memory_policy_drw() {
+        for (policy, row1) in [
+            (MemoryPolicy::Wrap, [true, true, true, true]),
+            (MemoryPolicy::Clamp, [true, false, false, false]),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.ir = 0xFFF;
+            cpu.ram[0xFFF] = 0x80;
+            cpu.ram[0x000] = 0xF0;
+
+            run_opcode(&mut cpu, 0xD012);
+            assert_eq!(cpu.screen[0..4], [true, false, false, false]);
+            assert_eq!(cpu.screen[64..68], row1);
+        }
+
+        let mut cpu = cpu_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0xFFF;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xD0, 0x12]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x1000 })));
+        assert!(!cpu.screen.contains(&true));
+    }
+
+    #[test]
+    fn test_memory_policy_fx33() {
+        for (policy, expected) in [
+            (MemoryPolicy::Wrap, [3, 1, 2]),
+            (MemoryPolicy::Clamp, [0, 1, 3]),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.ir = 0xFFE;
+            cpu.ram[0x000] = 0;
+            cpu.vx[0x0] = 123;
+
+            run_opcode(&mut cpu, 0xF033);
+            assert_eq!([cpu.ram[0x000], cpu.ram[0xFFE], cpu.ram[0xFFF]], expected);
+        }
+
+        let mut cpu = cpu_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0xFFE;
+        cpu.vx[0x0] = 123;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xF0, 0x33]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x1000 })));
+        assert_eq!(cpu.ram[0xFFE..0x1000], [0, 0]);
+    }
+
+    #[test]
+    fn test_memory_policy_fx55() {
+        for (policy, expected, ir) in [
+            (MemoryPolicy::Wrap, [1, 2, 3], 0x002),
+            (MemoryPolicy::Clamp, [3, 0, 0], 0xFFF),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.quirks.load_store_increments_i = true;
+            cpu.ir = 0xFFF;
+            cpu.ram[0x000..0x002].fill(0);
+            cpu.vx[0x0..0x3].copy_from_slice(&[1, 2, 3]);
+
+            run_opcode(&mut cpu, 0xF255);
+            assert_eq!([cpu.ram[0xFFF], cpu.ram[0x000], cpu.ram[0x001]], expected);
+            assert_eq!(cpu.ir, ir);
+        }
+
+        let mut cpu = cpu_with_policy(MemoryPolicy::Fault);
+        cpu.quirks.load_store_increments_i = true;
+        cpu.ir = 0xFFF;
+        cpu.vx[0x0..0x3].copy_from_slice(&[1, 2, 3]);
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xF2, 0x55]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x1000 })));
+        assert_eq!(cpu.ram[0xFFF], 0);
+        assert_eq!(cpu.ir, 0xFFF);
+    }
+
+    #[test]
+    fn test_memory_policy_fx65() {
+        for (policy, expected) in [
+            (MemoryPolicy::Wrap, [0xAA, 0xBB, 0xCC]),
+            (MemoryPolicy::Clamp, [0xAA, 0xAA, 0xAA]),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.ir = 0xFFF;
+            cpu.ram[0xFFF] = 0xAA;
+            cpu.ram[0x000..0x002].copy_from_slice(&[0xBB, 0xCC]);
+
+            run_opcode(&mut cpu, 0xF265);
+            assert_eq!(cpu.vx[0x0..0x3], expected);
+        }
+
+        let mut cpu = cpu_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0xFFF;
+        cpu.ram[0xFFF] = 0xAA;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xF2, 0x65]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x1000 })));
+        assert_eq!(cpu.vx[0x0..0x3], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_memory_policy_fx1e() {
+        for (policy, ir) in [
+            (MemoryPolicy::Wrap, 0x001),
+            (MemoryPolicy::Fault, 0x1001),
+            (MemoryPolicy::Clamp, 0xFFF),
+        ] {
+            let mut cpu = cpu_with_policy(policy);
+            cpu.ir = 0xFFF;
+            cpu.vx[0x0] = 2;
+
+            run_opcode(&mut cpu, 0xF01E);
+            assert_eq!(cpu.ir, ir);
+        }
+
+        // under Fault the bad I is only reported when it is used
+        let mut cpu = cpu_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0x1001;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xF0, 0x65]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x1001 })));
+    }
+
+    fn xo_with_policy(policy: MemoryPolicy) -> Cpu {
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.memory_policy = policy;
+        cpu
+    }
+
+    #[test]
+    fn test_memory_policy_5xy2_5xy3() {
+        for (policy, stored, loaded) in [
+            (MemoryPolicy::Wrap, [1, 2, 3], [0xAA, 0xBB, 0xCC]),
+            (MemoryPolicy::Clamp, [3, 0, 0], [0xAA, 0xAA, 0xAA]),
+        ] {
+            let mut cpu = xo_with_policy(policy);
+            cpu.ir = 0xFFFF;
+            cpu.vx[0x1..0x4].copy_from_slice(&[1, 2, 3]);
+
+            run_opcode(&mut cpu, 0x5132);
+            assert_eq!([cpu.ram[0xFFFF], cpu.ram[0x000], cpu.ram[0x001]], stored);
+
+            cpu.ram[0xFFFF] = 0xAA;
+            cpu.ram[0x000..0x002].copy_from_slice(&[0xBB, 0xCC]);
+            run_opcode(&mut cpu, 0x5463);
+            assert_eq!(cpu.vx[0x4..0x7], loaded);
+        }
+
+        let mut cpu = xo_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0xFFFF;
+        cpu.vx[0x1..0x4].copy_from_slice(&[1, 2, 3]);
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x51, 0x32]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x10000 })));
+        assert_eq!(cpu.ram[0xFFFF], 0);
+
+        cpu.pc = 0x200;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x54, 0x63]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x10000 })));
+        assert_eq!(cpu.vx[0x4..0x7], [0, 0, 0]);
+    }
+
+    #[test]
+    fn test_memory_policy_f002() {
+        for (policy, tail) in [(MemoryPolicy::Wrap, 0x11), (MemoryPolicy::Clamp, 0xFF)] {
+            let mut cpu = xo_with_policy(policy);
+            cpu.ir = 0xFFF8;
+            cpu.ram[0xFFF8..0x10000].fill(0xFF);
+            cpu.ram[0x000..0x008].fill(0x11);
+
+            run_opcode(&mut cpu, 0xF002);
+            assert_eq!(cpu.pattern[..8], [0xFF; 8]);
+            assert_eq!(cpu.pattern[8..], [tail; 8]);
+        }
+
+        let mut cpu = xo_with_policy(MemoryPolicy::Fault);
+        cpu.ir = 0xFFF8;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0xF0, 0x02]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x10000 })));
+        assert_eq!(cpu.pattern, DEFAULT_PATTERN);
+    }
+
+    #[test]
+    fn test_outcome_display_changed() {
+        let mut cpu = Cpu::init();
+        cpu.ram[0x200] = 0x00;
+        cpu.ram[0x201] = 0xE0;
+        cpu.ram[0x202] = 0xD0;
+        cpu.ram[0x203] = 0x01;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::DisplayChanged);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::DisplayChanged);
+    }
+
+    #[test]
+    fn test_outcome_waiting_for_key() {
+        let mut cpu = Cpu::init();
+        cpu.ram[0x200] = 0xF1;
+        cpu.ram[0x201] = 0x0A;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::WaitingForKey);
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.kp[0x7] = true;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Continue);
+        assert_eq!(cpu.vx[0x1], 0x7);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn test_outcome_sound() {
+        let mut cpu = Cpu::init();
+        cpu.st = 0;
+        cpu.vx[0x1] = 0x10;
+        cpu.ram[0x200] = 0xF1;
+        cpu.ram[0x201] = 0x18;
+        cpu.ram[0x202] = 0xF2;
+        cpu.ram[0x203] = 0x18;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::SoundStarted);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::SoundStopped);
+    }
+
+    #[test]
+    fn test_outcome_halted() {
+        let mut cpu = Cpu::init();
+        cpu.ram[0x200] = 0x12;
+        cpu.ram[0x201] = 0x00;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_outcome_sys_call() {
+        let mut cpu = Cpu::init();
+        cpu.ram[0x200] = 0x03;
+        cpu.ram[0x201] = 0x45;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::SysCall(0x345));
+    }
+
+    #[test]
+    fn test_run_frame() {
+        let mut cpu = Cpu::init();
+        cpu.dt = 10;
+        cpu.st = 1;
+
+        // ADD V0, 1 ; JP 0x200
+        cpu.ram[0x200] = 0x70;
+        cpu.ram[0x201] = 0x01;
+        cpu.ram[0x202] = 0x12;
+        cpu.ram[0x203] = 0x00;
+
+        let frame = cpu.run_frame(10).unwrap();
+
+        assert_eq!(frame.instructions, 10);
+        assert_eq!(cpu.vx[0x0], 5);
+        assert_eq!(cpu.dt, 9);
+        assert_eq!(cpu.st, 0);
+        assert!(!frame.sound_playing);
+        assert!(!frame.display_changed);
+    }
+
+    #[test]
+    fn test_run_frame_waiting_for_key() {
+        let mut cpu = Cpu::init();
+        cpu.dt = 10;
+
+        cpu.ram[0x200] = 0x00;
+        cpu.ram[0x201] = 0xE0;
+        cpu.ram[0x202] = 0xF0;
+        cpu.ram[0x203] = 0x0A;
+
+        let frame = cpu.run_frame(10).unwrap();
+
+        assert_eq!(frame.instructions, 2);
+        assert!(frame.display_changed);
+        assert!(frame.waiting_for_key);
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.dt, 9);
+    }
+
+    fn run_opcode<R: RandomSource>(cpu: &mut Cpu<R>, opcode: u16) -> StepOutcome {
+        let addr = cpu.pc as usize;
+        cpu.ram[addr] = (opcode >> 8) as u8;
+        cpu.ram[addr + 1] = opcode as u8;
+
+        cpu.step().unwrap()
+    }
+
+    #[test]
+    fn test_quirk_shift_uses_vy() {
+        for shift_uses_vy in [false, true] {
+            let mut cpu = Cpu::init();
+            cpu.quirks.shift_uses_vy = shift_uses_vy;
+            cpu.vx[0x1] = 0x81;
+            cpu.vx[0x2] = 0x06;
+
+            run_opcode(&mut cpu, 0x8126);
+
+            if shift_uses_vy {
+                assert_eq!(cpu.vx[0x1], 0x03);
+                assert_eq!(cpu.vx[0xF], 0);
+            } else {
+                assert_eq!(cpu.vx[0x1], 0x40);
+                assert_eq!(cpu.vx[0xF], 1);
+            }
+
+            cpu.vx[0x1] = 0x81;
+            run_opcode(&mut cpu, 0x812E);
+
+            if shift_uses_vy {
+                assert_eq!(cpu.vx[0x1], 0x0C);
+                assert_eq!(cpu.vx[0xF], 0);
+            } else {
+                assert_eq!(cpu.vx[0x1], 0x02);
+                assert_eq!(cpu.vx[0xF], 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quirk_load_store_increments_i() {
+        for increments in [false, true] {
+            let mut cpu = Cpu::init();
+            cpu.quirks.load_store_increments_i = increments;
+            cpu.ir = 0x300;
+
+            run_opcode(&mut cpu, 0xF355);
+            assert_eq!(cpu.ir, if increments { 0x304 } else { 0x300 });
+
+            run_opcode(&mut cpu, 0xF165);
+            assert_eq!(cpu.ir, if increments { 0x306 } else { 0x300 });
+        }
+    }
+
+    #[test]
+    fn test_quirk_jump_uses_vx() {
+        for jump_uses_vx in [false, true] {
+            let mut cpu = Cpu::init();
+            cpu.quirks.jump_uses_vx = jump_uses_vx;
+            cpu.vx[0x0] = 0x10;
+            cpu.vx[0x3] = 0x20;
+
+            run_opcode(&mut cpu, 0xB300);
+
+            assert_eq!(cpu.pc, if jump_uses_vx { 0x320 } else { 0x310 });
+        }
+    }
+
+    #[test]
+    fn test_quirk_vf_reset() {
+        for vf_reset in [false, true] {
+            for opcode in [0x8121, 0x8122, 0x8123] {
+                let mut cpu = Cpu::init();
+                cpu.quirks.vf_reset = vf_reset;
+                cpu.vx[0xF] = 0x1;
+
+                run_opcode(&mut cpu, opcode);
+
+                assert_eq!(cpu.vx[0xF], if vf_reset { 0 } else { 1 });
+            }
+        }
+    }
+
+    #[test]
+    fn test_quirk_clip_sprites() {
+        for clip_sprites in [false, true] {
+            let mut cpu = Cpu::init();
+            cpu.quirks.clip_sprites = clip_sprites;
+            cpu.ir = 0x300;
+            cpu.ram[0x300] = 0xFF;
+            cpu.ram[0x301] = 0xFF;
+            cpu.vx[0x0] = 60;
+            cpu.vx[0x1] = 31;
+
+            run_opcode(&mut cpu, 0xD012);
+
+            // bottom right corner is drawn either way
+            assert!(cpu.screen[31 * 64 + 63]);
+            // the rest wraps around to the left edge and the top
+            assert_eq!(cpu.screen[31 * 64], !clip_sprites);
+            assert_eq!(cpu.screen[60], !clip_sprites);
+            assert_eq!(cpu.screen[0], !clip_sprites);
+        }
+    }
+
+    #[test]
+    fn test_quirk_clip_sprites_wraps_start() {
+        let mut cpu = Cpu::init();
+        cpu.quirks.clip_sprites = true;
+        cpu.ir = 0x300;
+        cpu.ram[0x300] = 0x80;
+        cpu.vx[0x0] = 64 + 3;
+        cpu.vx[0x1] = 32 + 2;
+
+        run_opcode(&mut cpu, 0xD011);
+
+        assert!(cpu.screen[2 * 64 + 3]);
+    }
+
+    #[test]
+    fn test_quirk_display_wait() {
+        for display_wait in [false, true] {
+            let mut cpu = Cpu::init();
+            cpu.quirks.display_wait = display_wait;
+
+            // DRW V0, V0, 1 ; DRW V0, V0, 1 ; JP 0x200
+            let program = [0xD0, 0x01, 0xD0, 0x01, 0x12, 0x00];
+            cpu.ram[0x200..0x206].copy_from_slice(&program);
+
+            let frame = cpu.run_frame(10).unwrap();
+
+            assert!(frame.display_changed);
+            assert_eq!(frame.instructions, if display_wait { 1 } else { 10 });
+        }
+    }
+
+    #[test]
+    fn test_for_platform() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+
+        assert_eq!(cpu.quirks, Platform::CosmacVip.quirks());
+        assert_eq!(cpu.memory_size(), 0x1000);
+        assert_eq!(cpu.ram[0x000..0x050], font::VIP);
+
+        let rom = include_bytes!("../roms/games/space_invaders.ch8");
+        cpu.load_rom_from_bytes(rom).unwrap();
+        assert_eq!(cpu.pc, 0x200);
+
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.pc = 0xFFF0;
+        cpu.ram[0xFFF0] = 0x00;
+        cpu.ram[0xFFF1] = 0xE0;
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::DisplayChanged);
+    }
+
+    #[test]
+    fn test_superchip_decode() {
+        assert!(matches!(Instruction::decode(0x00FF), Some(Instruction::SYS(0x0FF))));
+        assert!(matches!(
+            Instruction::decode_for(0x00FF, Platform::SuperChip),
+            Some(Instruction::HIGH)
+        ));
+        assert!(Instruction::decode(0xF130).is_none());
+        assert!(matches!(
+            Instruction::decode_for(0xF130, Platform::SuperChip),
+            Some(Instruction::LD(0x1, LDType::HF))
+        ));
+    }
+
+    #[test]
+    fn test_00fe_00ff() {
+        // LOW / HIGH
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.screen[0] = true;
+
+        assert_eq!(run_opcode(&mut cpu, 0x00FF), StepOutcome::ResolutionChanged);
+        assert!(cpu.hires);
+        assert_eq!((cpu.width, cpu.height), (128, 64));
+        assert!(!cpu.screen[0]);
+
+        assert_eq!(run_opcode(&mut cpu, 0x00FE), StepOutcome::ResolutionChanged);
+        assert!(!cpu.hires);
+        assert_eq!((cpu.width, cpu.height), (64, 32));
+    }
+
+    #[test]
+    fn test_00cn() {
+        // SCD n
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.screen[5] = true;
+        cpu.screen[64 + 5] = true;
+
+        run_opcode(&mut cpu, 0x00C3);
+
+        assert!(!cpu.screen[5]);
+        assert!(!cpu.screen[64 + 5]);
+        assert!(cpu.screen[3 * 64 + 5]);
+        assert!(cpu.screen[4 * 64 + 5]);
+    }
+
+    #[test]
+    fn test_00fb_00fc() {
+        // SCR / SCL
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.screen[64 + 62] = true;
+
+        run_opcode(&mut cpu, 0x00FB);
+
+        // scrolled off the right edge
+        assert!(!cpu.screen.iter().any(|&pixel| pixel));
+
+        cpu.screen[64 + 6] = true;
+        run_opcode(&mut cpu, 0x00FC);
+
+        assert!(!cpu.screen[64 + 6]);
+        assert!(cpu.screen[64 + 2]);
+    }
+
+    #[test]
+    fn test_00fd() {
+        // EXIT
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+
+        assert_eq!(run_opcode(&mut cpu, 0x00FD), StepOutcome::Halted);
+    }
+
+    #[test]
+    fn test_dxy0() {
+        // DRW x, y, 0
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        run_opcode(&mut cpu, 0x00FF);
+        cpu.ir = 0x300;
+        for i in 0..32 {
+            cpu.ram[0x300 + i] = 0xFF;
+        }
+        cpu.vx[0x1] = 100;
+        cpu.vx[0x2] = 40;
+
+        run_opcode(&mut cpu, 0xD120);
+
+        let lit = cpu.screen.iter().filter(|&&pixel| pixel).count();
+        assert_eq!(lit, 16 * 16);
+        assert!(cpu.screen[40 * 128 + 100]);
+        assert!(cpu.screen[55 * 128 + 115]);
+        assert_eq!(cpu.vx[0xF], 0);
+
+        run_opcode(&mut cpu, 0xD120);
+
+        assert_eq!(cpu.vx[0xF], 1);
+        assert!(!cpu.screen.iter().any(|&pixel| pixel));
+    }
+
+    #[test]
+    fn test_fx30() {
+        // LD HF, x
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        cpu.vx[0x1] = 0x7;
+
+        run_opcode(&mut cpu, 0xF130);
+
+        assert_eq!(cpu.ir, 0x0A0 + 70);
+        assert_eq!(cpu.ram[cpu.ir as usize..(cpu.ir + 10) as usize], font::SUPERCHIP_BIG[70..80]);
+    }
+
+    // the top-left 8 pixels of the first `rows` screen rows, msb leftmost
+    fn screen_rows<R: RandomSource>(cpu: &Cpu<R>, rows: usize) -> Vec<u8> {
+        (0..rows)
+            .map(|row| {
+                let start = row * cpu.width as usize;
+                cpu.screen[start..start + 8]
+                    .iter()
+                    .fold(0, |byte, &pixel| (byte << 1) | pixel as u8)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_draw_small_fonts() {
+        for platform in [Platform::CosmacVip, Platform::Modern] {
+            for font in SmallFont::ALL {
+                let mut cpu = Cpu::for_platform(platform);
+                cpu.font = font;
+                cpu.load_font();
+
+                for digit in 0..16 {
+                    cpu.screen.fill(false);
+                    cpu.vx[0x0] = digit;
+                    run_opcode(&mut cpu, 0xF029);
+                    run_opcode(&mut cpu, 0xD115);
+
+                    let start = digit as usize * 5;
+                    assert_eq!(screen_rows(&cpu, 5), font.glyphs()[start..start + 5]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_big_fonts() {
+        for platform in [Platform::SuperChip, Platform::XoChip] {
+            for font in BigFont::ALL {
+                let mut cpu = Cpu::for_platform(platform);
+                cpu.big_font = Some(font);
+                cpu.load_font();
+
+                for digit in 0..16 {
+                    cpu.screen.fill(false);
+                    cpu.vx[0x0] = digit;
+                    run_opcode(&mut cpu, 0xF030);
+                    run_opcode(&mut cpu, 0xD11A);
+
+                    let start = digit as usize * 10;
+                    assert_eq!(screen_rows(&cpu, 10), font.glyphs()[start..start + 10]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fx75_fx85() {
+        // LD R, x / LD x, R
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        for i in 0..8 {
+            cpu.vx[i] = i as u8 + 1;
+        }
+
+        run_opcode(&mut cpu, 0xF775);
+        cpu.vx = [0; 16];
+        run_opcode(&mut cpu, 0xF385);
+
+        assert_eq!(cpu.rpl[0..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(cpu.vx[0..8], [1, 2, 3, 4, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_xochip_decode() {
+        assert!(Instruction::decode_for(0xF000, Platform::SuperChip).is_none());
+        assert!(matches!(
+            Instruction::decode_for(0xF000, Platform::XoChip),
+            Some(Instruction::LD(0, LDType::LongAddr))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0x5122, Platform::XoChip),
+            Some(Instruction::LD(0x1, LDType::ToIRange(0x2)))
+        ));
+        assert!(Instruction::decode_for(0x5122, Platform::Modern).is_none());
+    }
+
+    #[test]
+    fn test_f000_nnnn() {
+        // LD I, long
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.ram[0x200..0x204].copy_from_slice(&[0xF0, 0x00, 0xE1, 0x23]);
+
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.ir, 0xE123);
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_skip_over_long_load() {
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        // SE V0, 0 ; LD I, long 0xE123
+        cpu.ram[0x200..0x206].copy_from_slice(&[0x30, 0x00, 0xF0, 0x00, 0xE1, 0x23]);
+
+        cpu.step().unwrap();
+
+        assert_eq!(cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn test_0nnn_machine_code() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        // R7 = 0xEF0 ; V0 = 0x42 ; first display byte = 0xFF ; SEP R4
+        cpu.ram[0x300..0x30D].copy_from_slice(&[
+            0xF8, 0x0E, 0xB7, 0xF8, 0xF0, 0xA7, 0xF8, 0x42, 0x57, 0xF8, 0xFF, 0x5B, 0xD4,
+        ]);
+        cpu.vx[0x1] = 0x17;
+
+        assert_eq!(run_opcode(&mut cpu, 0x0300), StepOutcome::DisplayChanged);
+
+        assert_eq!(cpu.vx[0x0], 0x42);
+        assert_eq!(cpu.vx[0x1], 0x17);
+        assert_eq!(cpu.pc, 0x202);
+        assert!(cpu.screen[0..8].iter().all(|&pixel| pixel));
+        assert!(!cpu.screen[8]);
+
+        // other platforms leave 0NNN to the host
+        let mut cpu = Cpu::for_platform(Platform::SuperChip);
+        assert_eq!(run_opcode(&mut cpu, 0x0300), StepOutcome::SysCall(0x300));
+    }
+
+    #[test]
+    fn test_0nnn_never_returns() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        // BR 0x00, back to itself
+        cpu.ram[0x300..0x302].copy_from_slice(&[0x30, 0x00]);
+
+        assert!(matches!(
+            cpu.execute(Instruction::SYS(0x300)),
+            Err(Error::MachineCodeTimeout { addr: 0x300 })
+        ));
+    }
+
+    #[test]
+    fn test_vip_timing_frame() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.timing = Some(VipTiming::default());
+        // LD V0, 1 ; JP 0x200
+        cpu.ram[0x200..0x204].copy_from_slice(&[0x60, 0x01, 0x12, 0x00]);
+
+        let frame = cpu.run_frame(1).unwrap();
+
+        // 46 + 52 cycles a loop against 2598 for the frame, the speed
+        // argument is ignored. the 48 cycles of overrun carry over
+        assert_eq!(frame.instructions, 54);
+        let timing = cpu.timing.unwrap();
+        assert_eq!(timing.frame_cycles, 48);
+        assert_eq!(timing.total_cycles, 27 * 98 + 1070);
+    }
+
+    #[test]
+    fn test_vip_timing_drw_waits() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.quirks.display_wait = false;
+        cpu.timing = Some(VipTiming::default());
+        // DRW V0, V0, 1 ; JP 0x200
+        cpu.ram[0x200..0x204].copy_from_slice(&[0xD0, 0x01, 0x12, 0x00]);
+
+        let frame = cpu.run_frame(100).unwrap();
+
+        assert_eq!(frame.instructions, 1);
+        let timing = cpu.timing.unwrap();
+        assert_eq!(timing.frame_cycles, 0);
+        assert_eq!(timing.total_cycles, crate::timing::CYCLES_PER_FRAME as u64);
+    }
+
+    #[test]
+    fn test_memory_mapped_display_and_registers() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.memory_mapped = true;
+        cpu.ir = 0x000;
+        cpu.ram[0x000] = 0xF0;
+
+        // DRW V0, V0, 1 ; LD I, 0xF00 ; LD V0, [I]
+        run_opcode(&mut cpu, 0xD001);
+        run_opcode(&mut cpu, 0xAF00);
+        run_opcode(&mut cpu, 0xF065);
+
+        assert_eq!(cpu.vx[0x0], 0xF0);
+        assert_eq!(cpu.ram[0xEF0], 0xF0);
+
+        // LD [I], V1 from 0xF07 puts V1 at the start of the second row
+        cpu.ir = 0xF07;
+        cpu.vx[0x1] = 0xAA;
+        assert_eq!(run_opcode(&mut cpu, 0xF155), StepOutcome::DisplayChanged);
+        assert!(cpu.screen[64]);
+        assert!(!cpu.screen[65]);
+        assert!(cpu.screen[66]);
+
+        // LD B, V3 straight into V0-V2
+        cpu.vx[0x3] = 123;
+        cpu.ir = 0xEF0;
+        run_opcode(&mut cpu, 0xF333);
+        assert_eq!(cpu.vx[0x0..0x3], [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_memory_mapped_stack() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.memory_mapped = true;
+
+        // CALL 0x300, then patch the return address to 0x400 through ram
+        run_opcode(&mut cpu, 0x2300);
+        let slot = VIP_STACK_ADDR + 2 * (cpu.sp - 1);
+        assert_eq!(cpu.ram[slot..slot + 2], [0x02, 0x02]);
+
+        cpu.vx[0x0] = 0x04;
+        cpu.vx[0x1] = 0x00;
+        cpu.ir = slot as u16;
+        run_opcode(&mut cpu, 0xF155);
+        run_opcode(&mut cpu, 0x00EE);
+
+        assert_eq!(cpu.pc, 0x400);
+    }
+
+    #[test]
+    fn test_memory_mapped_stack_depth() {
+        // only the VIP's 12 entries are mapped, the bytes after them are
+        // left alone
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.memory_mapped = true;
+        cpu.ram[VIP_STACK_ADDR + 24] = 0xAB;
+        run_opcode(&mut cpu, 0x2200);
+        assert_eq!(cpu.ram[VIP_STACK_ADDR + 24], 0xAB);
+
+        // a mapped modern stack overflows once the ram slots are full
+        let mut cpu = Cpu::for_platform(Platform::Modern);
+        cpu.memory_mapped = true;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x22, 0x00]);
+        for _ in 0..VIP_STACK_SLOTS {
+            cpu.step().unwrap();
+        }
+        assert!(matches!(cpu.step(), Err(Error::StackOverflow { addr: 0x200 })));
+        assert_eq!(cpu.ram[VIP_VX_ADDR], 0);
+    }
+
+    #[test]
+    fn test_hires_rom() {
+        let mut cpu = Cpu::for_platform(Platform::HiRes);
+        let mut rom = [0x00; 0xC4];
+        rom[0x00..0x02].copy_from_slice(&[0x12, 0x60]);
+        // LD V0, 60 ; DRW V0, V0, 1
+        rom[0xC0..0xC4].copy_from_slice(&[0x60, 0x3C, 0xD0, 0x01]);
+        cpu.ir = 0x000;
+        cpu.ram[0x000] = 0x80;
+
+        cpu.load_rom_from_bytes(&rom).unwrap();
+
+        assert_eq!(cpu.pc, 0x2C0);
+        assert_eq!((cpu.width, cpu.height), (64, 64));
+
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.screen[60 * 64 + 60]);
+
+        // 0230 clears the two-page display
+        assert_eq!(run_opcode(&mut cpu, 0x0230), StepOutcome::DisplayChanged);
+        assert!(!cpu.screen[60 * 64 + 60]);
+    }
+
+    #[test]
+    fn test_chip8x_decode() {
+        assert!(matches!(
+            Instruction::decode_for(0x02A0, Platform::Chip8X),
+            Some(Instruction::BGC)
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0xB123, Platform::Chip8X),
+            Some(Instruction::COL(0x1, 0x2, 0x3))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0xE3F2, Platform::Chip8X),
+            Some(Instruction::SKP2(0x3))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0xE3F5, Platform::Chip8X),
+            Some(Instruction::SKNP2(0x3))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0x02A0, Platform::CosmacVip),
+            Some(Instruction::SYS(0x2A0))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0xB123, Platform::CosmacVip),
+            Some(Instruction::JP(JPType::FromV0(0x123)))
+        ));
+        assert!(Instruction::decode_for(0xE3F2, Platform::CosmacVip).is_none());
+    }
+
+    #[test]
+    fn test_bxy0() {
+        // COL x, y, 0
+        let mut cpu = Cpu::for_platform(Platform::Chip8X);
+        cpu.vx[0x1] = 0x12; // columns 2 and 3
+        cpu.vx[0x2] = 0x4; // green
+        cpu.vx[0x3] = 0x01; // rows 4 to 7
+
+        run_opcode(&mut cpu, 0xB130);
+
+        for row in 0..32 {
+            for col in 0..8 {
+                let expected = if (4..8).contains(&row) && (2..4).contains(&col) {
+                    0x4
+                } else {
+                    CHIP8X_RED
+                };
+                assert_eq!(cpu.colours[row * 8 + col], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bxyn() {
+        // COL x, y, n
+        let mut cpu = Cpu::for_platform(Platform::Chip8X);
+        cpu.vx[0x1] = 0x07;
+        cpu.vx[0x2] = 0x2;
+        cpu.vx[0x3] = 30;
+
+        // the rows past the bottom of the display are dropped
+        run_opcode(&mut cpu, 0xB134);
+
+        assert_eq!(cpu.colours[30 * 8 + 7], 0x2);
+        assert_eq!(cpu.colours[31 * 8 + 7], 0x2);
+        assert_eq!(cpu.colours[29 * 8 + 7], CHIP8X_RED);
+        assert_eq!(cpu.colours[31 * 8 + 6], CHIP8X_RED);
+    }
+
+    #[test]
+    fn test_02a0_and_draw() {
+        // BGC
+        let mut cpu = Cpu::for_platform(Platform::Chip8X);
+        assert_eq!(cpu.pc, 0x300);
+
+        assert_eq!(run_opcode(&mut cpu, 0x02A0), StepOutcome::DisplayChanged);
+        assert_eq!(cpu.background, 1);
+
+        cpu.screen[0] = true;
+        cpu.screen[8] = true;
+        cpu.colours[1] = 0x7;
+
+        let mut frame = vec![0; 64 * 32 * 4];
+        cpu.draw(&mut frame);
+
+        assert_eq!(frame[0..4], CHIP8X_FOREGROUNDS[CHIP8X_RED as usize]);
+        assert_eq!(frame[4..8], CHIP8X_BACKGROUNDS[1]);
+        assert_eq!(frame[32..36], CHIP8X_FOREGROUNDS[0x7]);
+    }
+
+    #[test]
+    fn test_exf2_exf5() {
+        // SKP2 x / SKNP2 x
+        let mut cpu = Cpu::for_platform(Platform::Chip8X);
+        cpu.vx[0x1] = 0xA;
+        cpu.kp[0xA] = true;
+
+        run_opcode(&mut cpu, 0xE1F2);
+        assert_eq!(cpu.pc, 0x302);
+
+        cpu.kp2[0xA] = true;
+        run_opcode(&mut cpu, 0xE1F2);
+        assert_eq!(cpu.pc, 0x306);
+
+        run_opcode(&mut cpu, 0xE1F5);
+        assert_eq!(cpu.pc, 0x308);
+    }
+
+    // without std nothing allocates the buffer, so the test leaks one
+    fn megachip_cpu() -> Cpu {
+        #[allow(unused_mut)]
+        let mut cpu = Cpu::for_platform(Platform::MegaChip);
+        #[cfg(not(feature = "std"))]
+        {
+            cpu.mega = Some(Box::leak(Box::new(MegaChip::new())));
+        }
+        cpu
+    }
+
+    fn mega(cpu: &Cpu) -> &MegaChip {
+        cpu.mega.as_deref().unwrap()
+    }
+
+    #[test]
+    fn test_megachip_buffer() {
+        // only MegaChip carries the framebuffer
+        assert!(Cpu::for_platform(Platform::XoChip).mega.is_none());
+        assert!(megachip_cpu().mega.is_some());
+
+        let mut cpu = megachip_cpu();
+        cpu.mega = None;
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x00, 0x11]);
+        assert!(matches!(cpu.step(), Err(Error::MegaChipUnavailable { addr: 0x200 })));
+    }
+
+    #[test]
+    fn test_megachip_decode() {
+        assert!(matches!(
+            Instruction::decode_for(0x0011, Platform::MegaChip),
+            Some(Instruction::MEGAON)
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0x0240, Platform::MegaChip),
+            Some(Instruction::LDPAL(0x40))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0x00B3, Platform::MegaChip),
+            Some(Instruction::SCU(0x3))
+        ));
+        assert!(matches!(
+            Instruction::decode_for(0x0011, Platform::SuperChip),
+            Some(Instruction::SYS(0x011))
+        ));
+    }
+
+    #[test]
+    fn test_0011_0010() {
+        // MEGAON / MEGAOFF
+        let mut cpu = megachip_cpu();
+
+        assert_eq!(run_opcode(&mut cpu, 0x0011), StepOutcome::ResolutionChanged);
+        assert!(mega(&cpu).enabled);
+        assert_eq!((cpu.width, cpu.height), (256, 192));
+
+        assert_eq!(run_opcode(&mut cpu, 0x0010), StepOutcome::ResolutionChanged);
+        assert!(!mega(&cpu).enabled);
+        assert_eq!((cpu.width, cpu.height), (64, 32));
+    }
+
+    #[test]
+    fn test_01nn_nnnn() {
+        // LDHI I, nn, long
+        let mut cpu = megachip_cpu();
+        cpu.ram[0x200..0x204].copy_from_slice(&[0x01, 0x00, 0xE1, 0x23]);
+        cpu.ram[0x204..0x208].copy_from_slice(&[0x01, 0x01, 0x00, 0x00]);
+
+        cpu.step().unwrap();
+        assert_eq!(cpu.ir, 0xE123);
+        assert_eq!(cpu.pc, 0x204);
+
+        // only the first 64K are backed
+        assert!(matches!(
+            cpu.step(),
+            Err(Error::MemoryOutOfBounds { addr: 0x10000 })
+        ));
+    }
+
+    #[test]
+    fn test_megachip_sprites() {
+        // LDPAL nn / SPRW nn / SPRH nn / BMODE n / CCOL nn / DRW x, y, n
+        let mut cpu = megachip_cpu();
+        cpu.ir = 0x1000;
+        cpu.ram[0x1000..0x1008].copy_from_slice(&[
+            0xFF, 0xFF, 0x00, 0x00, // 1: red
+            0xFF, 0x00, 0x00, 0xFF, // 2: blue
+        ]);
+        // a 2x2 sprite with a transparent corner
+        cpu.ram[0x1100..0x1104].copy_from_slice(&[1, 2, 0, 1]);
+        cpu.vx[0x0] = 10;
+        cpu.vx[0x1] = 20;
+
+        run_opcode(&mut cpu, 0x0011);
+        run_opcode(&mut cpu, 0x0202);
+        run_opcode(&mut cpu, 0x0302);
+        run_opcode(&mut cpu, 0x0402);
+        run_opcode(&mut cpu, 0x0901);
+        cpu.ir = 0x1100;
+        run_opcode(&mut cpu, 0xD010);
+
+        let at = |col: usize, row: usize| row * megachip::WIDTH + col;
+        assert_eq!(mega(&cpu).framebuffer[at(10, 20)], [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(mega(&cpu).framebuffer[at(11, 20)], [0x00, 0x00, 0xFF, 0xFF]);
+        assert_eq!(mega(&cpu).framebuffer[at(10, 21)], [0x00, 0x00, 0x00, 0xFF]);
+        assert_eq!(mega(&cpu).indices[at(11, 21)], 1);
+        assert_eq!(cpu.vx[0xF], 0);
+
+        // drawing again over index 1 collides, and blends half and half
+        run_opcode(&mut cpu, 0x0802);
+        run_opcode(&mut cpu, 0xD010);
+
+        assert_eq!(cpu.vx[0xF], 1);
+        assert_eq!(mega(&cpu).framebuffer[at(10, 20)], [0xFF, 0x00, 0x00, 0xFF]);
+        assert_eq!(mega(&cpu).framebuffer[at(11, 21)], [0xFF, 0x00, 0x00, 0xFF]);
+
+        let mut frame = vec![0; megachip::WIDTH * megachip::HEIGHT * 4];
+        cpu.draw(&mut frame);
+        assert_eq!(frame[at(10, 20) * 4..at(10, 20) * 4 + 4], [0xFF, 0x00, 0x00, 0xFF]);
+
+        run_opcode(&mut cpu, 0x00E0);
+        assert_eq!(mega(&cpu).indices[at(10, 20)], 0);
+    }
+
+    #[test]
+    fn test_060n_0700() {
+        // DIGISND n / STOPSND
+        let mut cpu = megachip_cpu();
+        cpu.ir = 0x2000;
+        cpu.ram[0x2000..0x2006].copy_from_slice(&[0x1F, 0x40, 0x00, 0x01, 0x00, 0x00]);
+
+        assert_eq!(run_opcode(&mut cpu, 0x0601), StepOutcome::SoundStarted);
+        let sample = mega(&cpu).sample.unwrap();
+        assert_eq!((sample.addr, sample.len, sample.rate), (0x2006, 0x100, 8000));
+        assert!(!sample.looping);
+
+        assert_eq!(run_opcode(&mut cpu, 0x0700), StepOutcome::SoundStopped);
+        assert!(mega(&cpu).sample.is_none());
+
+        cpu.ir = 0xFFFA;
+        cpu.ram[0xFFFA..0x10000].copy_from_slice(&[0x1F, 0x40, 0x00, 0x01, 0x00, 0x00]);
+        assert!(matches!(
+            cpu.execute(Instruction::DIGISND(0)),
+            Err(Error::MemoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_memory_policy_ldpal() {
+        for (policy, second) in [
+            (MemoryPolicy::Wrap, [0x11, 0x22, 0x33, 0x44]),
+            (MemoryPolicy::Clamp, [0xAA, 0xAA, 0xAA, 0xAA]),
+        ] {
+            let mut cpu = megachip_cpu();
+            cpu.memory_policy = policy;
+            cpu.ir = 0xFFFC;
+            cpu.ram[0xFFFC..0x10000].fill(0xAA);
+            cpu.ram[0x000..0x004].copy_from_slice(&[0x44, 0x11, 0x22, 0x33]);
+
+            run_opcode(&mut cpu, 0x0202);
+            assert_eq!(mega(&cpu).palette[1], [0xAA; 4]);
+            assert_eq!(mega(&cpu).palette[2], second);
+        }
+
+        let mut cpu = megachip_cpu();
+        cpu.ir = 0xFFFC;
+        cpu.ram[0xFFFC..0x10000].fill(0xAA);
+        cpu.ram[0x200..0x202].copy_from_slice(&[0x02, 0x02]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x10000 })));
+        assert_eq!(mega(&cpu).palette[1], [0x00, 0x00, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_memory_policy_megachip_drw() {
+        // a 1x2 sprite whose second row is past the end of memory
+        let setup = |policy| {
+            let mut cpu = megachip_cpu();
+            cpu.memory_policy = policy;
+            run_opcode(&mut cpu, 0x0011);
+            run_opcode(&mut cpu, 0x0301);
+            run_opcode(&mut cpu, 0x0402);
+            cpu.ir = 0xFFFF;
+            cpu.ram[0xFFFF] = 1;
+            cpu.ram[0x000] = 2;
+            cpu.vx[0x0] = 10;
+            cpu.vx[0x1] = 20;
+            cpu
+        };
+        let at = |col: usize, row: usize| row * megachip::WIDTH + col;
+
+        for (policy, second) in [(MemoryPolicy::Wrap, 2), (MemoryPolicy::Clamp, 1)] {
+            let mut cpu = setup(policy);
+            run_opcode(&mut cpu, 0xD010);
+            assert_eq!(mega(&cpu).indices[at(10, 20)], 1);
+            assert_eq!(mega(&cpu).indices[at(10, 21)], second);
+        }
+
+        let mut cpu = setup(MemoryPolicy::Fault);
+        let pc = cpu.pc as usize;
+        cpu.ram[pc..pc + 2].copy_from_slice(&[0xD0, 0x10]);
+        assert!(matches!(cpu.step(), Err(Error::MemoryOutOfBounds { addr: 0x10000 })));
+        assert_eq!(mega(&cpu).indices[at(10, 20)], 0);
+
+        // pixels past the right and bottom edges are dropped, not wrapped
+        let mut cpu = setup(MemoryPolicy::Wrap);
+        run_opcode(&mut cpu, 0x0302);
+        cpu.ir = 0x1000;
+        cpu.ram[0x1000..0x1004].fill(3);
+        cpu.vx[0x0] = 255;
+        cpu.vx[0x1] = 191;
+        run_opcode(&mut cpu, 0xD010);
+        assert_eq!(mega(&cpu).indices[at(255, 191)], 3);
+        assert!(mega(&cpu).indices[..at(255, 191)].iter().all(|&index| index == 0));
+    }
+
+    #[test]
+    fn test_f002_fx3a() {
+        // AUDIO / PITCH x
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        for (i, byte) in cpu.ram[0xE00..0xE10].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        cpu.ir = 0xE00;
+        cpu.vx[0x4] = 0x70;
+
+        run_opcode(&mut cpu, 0xF002);
+        run_opcode(&mut cpu, 0xF43A);
+
+        assert_eq!(cpu.pattern, core::array::from_fn(|i| i as u8));
+        assert_eq!(cpu.pitch, 0x70);
+        assert!(Instruction::decode_for(0xF002, Platform::SuperChip).is_none());
+        assert!(Instruction::decode_for(0xF43A, Platform::SuperChip).is_none());
+
+        cpu.ir = 0xFFF8;
+        assert!(matches!(
+            cpu.execute(Instruction::AUDIO),
+            Err(Error::MemoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fn01_dxyn_planes() {
+        // PLANE n / DRW x, y, n
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.ir = 0x300;
+        cpu.ram[0x300] = 0x80;
+        cpu.ram[0x301] = 0xC0;
+
+        run_opcode(&mut cpu, 0xF301);
+        assert_eq!(cpu.planes, 0x3);
+        run_opcode(&mut cpu, 0xD001);
+
+        // plane 1 gets the first byte, plane 2 the one after it
+        assert!(cpu.screen[0] && cpu.plane2[0]);
+        assert!(!cpu.screen[1] && cpu.plane2[1]);
+
+        run_opcode(&mut cpu, 0xF201);
+        run_opcode(&mut cpu, 0x00E0);
+
+        assert!(cpu.screen[0]);
+        assert!(!cpu.plane2.iter().any(|&pixel| pixel));
+    }
+
+    #[test]
+    fn test_draw_colors() {
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.screen[0] = true;
+        cpu.plane2[1] = true;
+        cpu.screen[2] = true;
+        cpu.plane2[2] = true;
+
+        let mut frame = vec![0; 64 * 32 * 4];
+        cpu.draw(&mut frame);
+
+        assert_eq!(frame[0..4], DEFAULT_PALETTE[1]);
+        assert_eq!(frame[4..8], DEFAULT_PALETTE[2]);
+        assert_eq!(frame[8..12], DEFAULT_PALETTE[3]);
+        assert_eq!(frame[12..16], DEFAULT_PALETTE[0]);
+    }
+
+    #[test]
+    fn test_5xy2_5xy3() {
+        // LD [I], x-y / LD x-y, [I]
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.ir = 0x8000;
+        cpu.vx[0x2..0x5].copy_from_slice(&[0xA, 0xB, 0xC]);
+
+        run_opcode(&mut cpu, 0x5242);
+        assert_eq!(cpu.ram[0x8000..0x8003], [0xA, 0xB, 0xC]);
+        assert_eq!(cpu.ir, 0x8000);
+
+        run_opcode(&mut cpu, 0x5422);
+        assert_eq!(cpu.ram[0x8000..0x8003], [0xC, 0xB, 0xA]);
+
+        run_opcode(&mut cpu, 0x5793);
+        assert_eq!(cpu.vx[0x7..0xA], [0xC, 0xB, 0xA]);
+    }
+
+    #[test]
+    fn test_00dn() {
+        // SCU n
+        let mut cpu = Cpu::for_platform(Platform::XoChip);
+        cpu.screen[3 * 64 + 5] = true;
+
+        run_opcode(&mut cpu, 0x00D2);
+
+        assert!(cpu.screen[64 + 5]);
+        assert!(!cpu.screen[3 * 64 + 5]);
+    }
 }
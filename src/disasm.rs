@@ -0,0 +1,665 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+
+use crate::cpu::{Instruction, JPType, LDType};
+use crate::error::{Error, Result};
+use crate::platform::Platform;
+use crate::syntax::Syntax;
+
+// why an address got a label, which also names it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LabelKind {
+    Jump,
+    Call,
+    // the start of a BNNN jump table
+    Table,
+    // pointed at by I := NNN
+    Data,
+}
+
+impl LabelKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            Self::Jump => "label",
+            Self::Call => "sub",
+            Self::Table => "table",
+            Self::Data => "data",
+        }
+    }
+}
+
+// a rom split into instructions and data bytes, covering every byte once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+    // `long` is the address word following an XO-CHIP long load or a
+    // MegaChip LDHI
+    Code {
+        addr: u16,
+        instruction: Instruction,
+        long: Option<u16>,
+    },
+    Data {
+        addr: u16,
+        byte: u8,
+    },
+}
+
+// recursive descent: only what some path from the entry point reaches
+// through jumps, calls and skips is code, everything else is data
+pub struct Disassembly {
+    pub platform: Platform,
+    pub items: Vec<Item>,
+    pub labels: BTreeMap<u16, LabelKind>,
+    start: u16,
+}
+
+impl Disassembly {
+    // fails if the rom doesn't fit in memory from the platform's start
+    // address, the way loading it would
+    pub fn new(rom: &[u8], platform: Platform) -> Result<Disassembly> {
+        let start = platform.start_addr();
+        let max = platform.memory_size() - start as usize;
+        if rom.len() > max {
+            return Err(Error::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        let end = start as usize + rom.len();
+        let word = |addr: usize| {
+            u16::from_be_bytes([rom[addr - start as usize], rom[addr + 1 - start as usize]])
+        };
+        let in_rom = |addr: usize, len: usize| addr >= start as usize && addr + len <= end;
+
+        // by offset into the rom: where instructions start, and which bytes
+        // they cover
+        let mut starts = vec![false; rom.len()];
+        let mut claimed = vec![false; rom.len()];
+        let mut labels = BTreeMap::new();
+        // addresses are usize until they're known to be in the rom, the
+        // instruction after the last one can be past 0xFFFF
+        let mut work = vec![start as usize, platform.entry_point(rom) as usize];
+        let add_label = |labels: &mut BTreeMap<u16, LabelKind>, addr: u16, kind| {
+            if in_rom(addr as usize, 1) {
+                let label = labels.entry(addr).or_insert(kind);
+                *label = (*label).min(kind);
+            }
+        };
+
+        while let Some(mut at) = work.pop() {
+            loop {
+                if !in_rom(at, 2) {
+                    break;
+                }
+                let offset = at - start as usize;
+                if starts[offset] {
+                    break;
+                }
+
+                let instruction = match Instruction::decode_for(word(at), platform) {
+                    Some(Instruction::RAW0) | None => break,
+                    Some(instruction) => instruction,
+                };
+                let len = length(instruction);
+                // a jump into the middle of another instruction is left as
+                // data, the listing can only show one of them
+                if !in_rom(at, len) || claimed[offset..offset + len].contains(&true) {
+                    break;
+                }
+                starts[offset] = true;
+                claimed[offset..offset + len].fill(true);
+                let next = at + len;
+
+                match instruction {
+                    Instruction::JP(JPType::Addr(nnn)) => {
+                        add_label(&mut labels, nnn, LabelKind::Jump);
+                        work.push(nnn as usize);
+                        break;
+                    }
+                    Instruction::JP(JPType::FromV0(nnn)) => {
+                        // V0 is unknown, but the table it indexes is almost
+                        // always a run of jumps
+                        add_label(&mut labels, nnn, LabelKind::Table);
+                        let mut entry = nnn as usize;
+                        while in_rom(entry, 2) && word(entry) & 0xF000 == 0x1000 {
+                            work.push(entry);
+                            entry += 2;
+                        }
+                        break;
+                    }
+                    Instruction::CALL(nnn) => {
+                        add_label(&mut labels, nnn, LabelKind::Call);
+                        work.push(nnn as usize);
+                    }
+                    Instruction::RET | Instruction::EXIT => break,
+                    Instruction::SE(..)
+                    | Instruction::SNE(..)
+                    | Instruction::SKP(_)
+                    | Instruction::SKNP(_)
+                    | Instruction::SKP2(_)
+                    | Instruction::SKNP2(_) => {
+                        // skipping a long load skips all four bytes
+                        let len = match in_rom(next, 2) {
+                            true => Instruction::decode_for(word(next), platform),
+                            false => None,
+                        };
+                        work.push(next + len.map_or(2, length));
+                    }
+                    Instruction::LD(_, LDType::Addr(nnn)) => {
+                        add_label(&mut labels, nnn, LabelKind::Data)
+                    }
+                    _ => {}
+                }
+                at = next;
+            }
+        }
+
+        let mut items = Vec::new();
+        let mut offset = 0;
+        while offset < rom.len() {
+            let addr = start + offset as u16;
+            if starts[offset] {
+                let instruction = Instruction::decode_for(word(addr as usize), platform).unwrap();
+                let long = match length(instruction) {
+                    4 => Some(word(addr as usize + 2)),
+                    _ => None,
+                };
+                items.push(Item::Code {
+                    addr,
+                    instruction,
+                    long,
+                });
+                offset += if long.is_some() { 4 } else { 2 };
+            } else {
+                items.push(Item::Data {
+                    addr,
+                    byte: rom[offset],
+                });
+                offset += 1;
+            }
+        }
+
+        // only addresses a line starts at can be labelled
+        let lines: Vec<u16> = items.iter().map(Item::addr).collect();
+        labels.retain(|addr, _| lines.binary_search(addr).is_ok());
+
+        Ok(Disassembly {
+            platform,
+            items,
+            labels,
+            start,
+        })
+    }
+
+    // the rom again, assembled from the items
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for item in &self.items {
+            match *item {
+                Item::Code {
+                    instruction, long, ..
+                } => {
+                    bytes.extend(instruction.encode_for(self.platform).to_be_bytes());
+                    if let Some(long) = long {
+                        bytes.extend(long.to_be_bytes());
+                    }
+                }
+                Item::Data { byte, .. } => bytes.push(byte),
+            }
+        }
+        bytes
+    }
+
+    pub fn label(&self, addr: u16) -> Option<String> {
+        match self.labels.get(&addr) {
+            // Octo requires a main label, see source
+            _ if addr == self.start => Some("main".to_string()),
+            Some(kind) => Some(format!("{}_{:03x}", kind.prefix(), addr)),
+            None => None,
+        }
+    }
+
+    // a label where there is one, the number otherwise
+    fn reference(&self, addr: u16) -> String {
+        self.label(addr)
+            .filter(|_| self.labels.contains_key(&addr))
+            .unwrap_or_else(|| format!("{:#x}", addr))
+    }
+
+    // Octo source that assembles back to the same rom. the program opens
+    // with `: main` so Octo drops the jump to main it would otherwise put
+    // at 0x200
+    pub fn source(&self) -> String {
+        let mut out = String::new();
+        // Octo assembles from 0x200 unless told otherwise
+        if self.start != 0x200 {
+            writeln!(out, ":org {:#x}", self.start).unwrap();
+        }
+        writeln!(out, ": main").unwrap();
+
+        for (i, item) in self.items.iter().enumerate() {
+            let addr = item.addr();
+            if addr != self.start && self.labels.contains_key(&addr) {
+                writeln!(out, "\n: {}", self.label(addr).unwrap()).unwrap();
+            }
+
+            match *item {
+                Item::Code {
+                    instruction, long, ..
+                } => {
+                    // a skip has to be followed by the instruction it skips
+                    let next_is_code = matches!(self.items.get(i + 1), Some(Item::Code { .. }));
+                    let text = self.octo(instruction, long, next_is_code);
+                    writeln!(out, "\t{:<24}# {:#05x}", text, addr).unwrap();
+                }
+                Item::Data { byte, .. } => {
+                    writeln!(
+                        out,
+                        "\t{:<24}# {}",
+                        format!("{:#04x}", byte),
+                        sprite_row(byte)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        out
+    }
+
+    fn octo(&self, instruction: Instruction, long: Option<u16>, next_is_code: bool) -> String {
+        let opcode = instruction.encode_for(self.platform);
+        let raw = || {
+            let mut bytes = opcode.to_be_bytes().to_vec();
+            bytes.extend(long.into_iter().flat_map(u16::to_be_bytes));
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:#04x}", byte)).collect();
+            bytes.join(" ")
+        };
+
+        match instruction {
+            Instruction::JP(JPType::Addr(nnn)) => format!("jump {}", self.reference(nnn)),
+            Instruction::JP(JPType::FromV0(nnn)) => format!("jump0 {}", self.reference(nnn)),
+            Instruction::LD(_, LDType::Addr(nnn)) => format!("i := {}", self.reference(nnn)),
+            // Octo calls a subroutine by naming it
+            Instruction::CALL(nnn) if self.labels.contains_key(&nnn) => self.reference(nnn),
+            Instruction::CALL(_) | Instruction::SYS(_) | Instruction::LDHI(_) => raw(),
+            Instruction::SE(..)
+            | Instruction::SNE(..)
+            | Instruction::SKP(_)
+            | Instruction::SKNP(_)
+                if !next_is_code =>
+            {
+                raw()
+            }
+            _ if opcode != instruction.encode() => raw(),
+            _ => instruction.display(Syntax::Octo).with_long(long).to_string(),
+        }
+    }
+
+    // an address per line, for reading rather than assembling
+    pub fn listing(&self, syntax: Syntax) -> String {
+        let mut out = String::new();
+
+        for item in &self.items {
+            let addr = item.addr();
+            if let Some(label) = self.label(addr).filter(|_| self.labels.contains_key(&addr)) {
+                writeln!(out, "\n{}:", label).unwrap();
+            }
+
+            match *item {
+                Item::Code {
+                    instruction, long, ..
+                } => {
+                    let opcode = instruction.encode_for(self.platform);
+                    write!(
+                        out,
+                        "{:#05x}  {:04x}  {}",
+                        addr,
+                        opcode,
+                        instruction.display(syntax)
+                    )
+                    .unwrap();
+                    if let Some(long) = long {
+                        write!(out, " {:#06x}", long).unwrap();
+                    }
+                    writeln!(out).unwrap();
+                }
+                Item::Data { byte, .. } => {
+                    writeln!(out, "{:#05x}  {:02x}    {}", addr, byte, sprite_row(byte)).unwrap();
+                }
+            }
+        }
+
+        out
+    }
+}
+
+impl Item {
+    pub fn addr(&self) -> u16 {
+        match *self {
+            Item::Code { addr, .. } | Item::Data { addr, .. } => addr,
+        }
+    }
+}
+
+// the XO-CHIP long load and MegaChip's LDHI carry an address word after the
+// opcode
+fn length(instruction: Instruction) -> usize {
+    match instruction {
+        Instruction::LD(_, LDType::LongAddr) | Instruction::LDHI(_) => 4,
+        _ => 2,
+    }
+}
+
+// a data byte drawn as the sprite row it would be
+fn sprite_row(byte: u8) -> String {
+    (0..8)
+        .map(|bit| if byte << bit & 0x80 != 0 { '#' } else { '.' })
+        .collect()
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // main: LD I, sprite / CALL draw / JP main
+    // draw: SE V0, 0 / DRW V0, V0, 2 / RET
+    // sprite: 2 bytes
+    const ROM: [u8; 14] = [
+        0xA2, 0x0C, 0x22, 0x06, 0x12, 0x00, 0x30, 0x00, 0xD0, 0x02, 0x00, 0xEE, 0x3C, 0x42,
+    ];
+
+    #[test]
+    fn test_rom_size() {
+        let rom = vec![0x60; 0xFE01];
+        assert!(matches!(
+            Disassembly::new(&rom, Platform::XoChip),
+            Err(Error::RomTooLarge {
+                size: 0xFE01,
+                max: 0xFE00
+            })
+        ));
+
+        // code that runs right up to the end of memory
+        let rom: Vec<u8> = [0x60, 0x00].repeat(0xFE00 / 2);
+        let dis = Disassembly::new(&rom, Platform::XoChip).unwrap();
+        assert_eq!(dis.items.len(), 0xFE00 / 2);
+        assert_eq!(dis.items.last().unwrap().addr(), 0xFFFE);
+        assert_eq!(dis.bytes(), rom);
+    }
+
+    #[test]
+    fn test_separates_code_and_data() {
+        let dis = Disassembly::new(&ROM, Platform::Modern).unwrap();
+
+        let code: Vec<u16> = dis
+            .items
+            .iter()
+            .filter(|item| matches!(item, Item::Code { .. }))
+            .map(Item::addr)
+            .collect();
+        assert_eq!(code, [0x200, 0x202, 0x204, 0x206, 0x208, 0x20A]);
+        assert_eq!(
+            dis.items[6],
+            Item::Data {
+                addr: 0x20C,
+                byte: 0x3C
+            }
+        );
+        assert_eq!(dis.bytes(), ROM);
+    }
+
+    #[test]
+    fn test_labels() {
+        let dis = Disassembly::new(&ROM, Platform::Modern).unwrap();
+
+        assert_eq!(dis.labels.get(&0x206), Some(&LabelKind::Call));
+        assert_eq!(dis.labels.get(&0x20C), Some(&LabelKind::Data));
+        let source = dis.source();
+        assert!(source.starts_with(": main\n"));
+        assert!(source.contains("i := data_20c"));
+        assert!(source.contains("\tsub_206 "));
+        assert!(source.contains("jump main"));
+        assert!(source.contains("\n: sub_206\n"));
+        assert!(source.contains("0x3c                    # ..####.."));
+    }
+
+    #[test]
+    fn test_skip_into_data_is_raw() {
+        // SE V0, 0 with nothing after it
+        let dis = Disassembly::new(&[0x30, 0x00], Platform::Modern).unwrap();
+
+        assert!(dis.source().contains("\t0x30 0x00 "));
+    }
+
+    #[test]
+    fn test_zero_word_does_not_end_code() {
+        // JP 0x206 over a zero word, then more code
+        let rom = [0x12, 0x06, 0x00, 0x00, 0xFF, 0xFF, 0x60, 0x01, 0x12, 0x08];
+        let dis = Disassembly::new(&rom, Platform::Modern).unwrap();
+
+        assert!(matches!(
+            dis.items.last(),
+            Some(Item::Code { addr: 0x208, .. })
+        ));
+        assert_eq!(dis.bytes(), rom);
+    }
+
+    #[test]
+    fn test_jump_table() {
+        // JP V0, 0x204 into a table of two jumps
+        let rom = [0xB2, 0x04, 0x00, 0x00, 0x12, 0x08, 0x12, 0x08, 0x12, 0x08];
+        let dis = Disassembly::new(&rom, Platform::Modern).unwrap();
+
+        assert_eq!(dis.labels.get(&0x204), Some(&LabelKind::Table));
+        assert_eq!(dis.labels.get(&0x208), Some(&LabelKind::Jump));
+        assert!(matches!(dis.items[1], Item::Data { addr: 0x202, .. }));
+        assert!(matches!(dis.items[4], Item::Code { addr: 0x206, .. }));
+    }
+
+    #[test]
+    fn test_megachip_ldhi() {
+        // LDHI I, 0x00, 0x0000 / LD V0, 1 / JP 0x208
+        let rom = [0x00, 0x11, 0x01, 0x00, 0x00, 0x00, 0x60, 0x01, 0x12, 0x08];
+        let dis = Disassembly::new(&rom, Platform::MegaChip).unwrap();
+
+        assert_eq!(
+            dis.items[1],
+            Item::Code {
+                addr: 0x202,
+                instruction: Instruction::LDHI(0x00),
+                long: Some(0x0000),
+            }
+        );
+        assert!(matches!(dis.items[2], Item::Code { addr: 0x206, .. }));
+        assert!(matches!(dis.items[3], Item::Code { addr: 0x208, .. }));
+        assert!(dis.source().contains("\t0x01 0x00 0x00 0x00 "));
+        assert_eq!(assemble(&dis.source()), (0x200, rom.to_vec()));
+    }
+
+    #[test]
+    fn test_skip_over_ldhi() {
+        // SE V0, 0 / LDHI I, 0x01, 0x2345 / RET
+        let rom = [0x30, 0x00, 0x01, 0x01, 0x23, 0x45, 0x00, 0xEE];
+        let dis = Disassembly::new(&rom, Platform::MegaChip).unwrap();
+
+        assert!(matches!(
+            dis.items.last(),
+            Some(Item::Code { addr: 0x206, .. })
+        ));
+        assert_eq!(assemble(&dis.source()), (0x200, rom.to_vec()));
+    }
+
+    #[test]
+    fn test_chip8x_org() {
+        // JP 0x304 over a zero word / LD V0, 1 / JP 0x306
+        let rom = [0x13, 0x04, 0x00, 0x00, 0x60, 0x01, 0x13, 0x06];
+        let source = Disassembly::new(&rom, Platform::Chip8X).unwrap().source();
+
+        assert!(source.starts_with(":org 0x300\n: main\n"));
+        assert_eq!(assemble(&source), (0x300, rom.to_vec()));
+    }
+
+    #[test]
+    fn test_source_reassembles() {
+        // i := long 0x210 / SE V0, 1 over i := long 0x212 / CALL 0x20E /
+        // JP 0x20C / SE V0, 0 with a zero word after it / RET / a sprite
+        let xochip = [
+            0xF0, 0x00, 0x02, 0x10, 0x30, 0x01, 0xF0, 0x00, 0x02, 0x12, 0x22, 0x0E, 0x12, 0x0C,
+            0x30, 0x00, 0x00, 0x00, 0x00, 0xEE, 0x3C, 0x42,
+        ];
+        let roms: [(&[u8], Platform); 7] = [
+            (&ROM, Platform::Modern),
+            (&xochip, Platform::XoChip),
+            (
+                include_bytes!("../roms/games/pong.ch8"),
+                Platform::CosmacVip,
+            ),
+            (
+                include_bytes!("../roms/games/tetris.ch8"),
+                Platform::CosmacVip,
+            ),
+            (
+                include_bytes!("../roms/games/space_invaders.ch8"),
+                Platform::CosmacVip,
+            ),
+            (
+                include_bytes!("../roms/games/connect4.ch8"),
+                Platform::CosmacVip,
+            ),
+            (
+                include_bytes!("../roms/tests/test_opcode.ch8"),
+                Platform::Modern,
+            ),
+        ];
+
+        for (rom, platform) in roms {
+            let source = Disassembly::new(rom, platform).unwrap().source();
+            assert_eq!(assemble(&source), (0x200, rom.to_vec()), "{}", source);
+        }
+
+        let source = Disassembly::new(&xochip, Platform::XoChip).unwrap().source();
+        assert!(source.contains("\ti := long 0x212 "));
+        assert!(source.contains("\t0x30 0x00 "));
+    }
+
+    // just enough of Octo to assemble what source() writes: :org, labels,
+    // raw bytes and the statements write_octo emits. returns the origin and
+    // the bytes from there on
+    fn assemble(source: &str) -> (u16, Vec<u8>) {
+        let lines: Vec<Vec<&str>> = source
+            .lines()
+            .map(|line| line.split('#').next().unwrap().split_whitespace().collect())
+            .filter(|tokens: &Vec<&str>| !tokens.is_empty())
+            .collect();
+        let is_number = |token: &str| token.starts_with("0x");
+
+        // the first pass places the labels
+        let mut origin = 0x200;
+        let mut addr = origin;
+        let mut labels = BTreeMap::new();
+        for tokens in &lines {
+            match tokens[..] {
+                [":org", n] => (origin, addr) = (number(n), number(n)),
+                [":", name] => assert!(labels.insert(name, addr).is_none()),
+                [first, ..] if is_number(first) => addr += tokens.len() as u16,
+                ["i", ":=", "long", _] => addr += 4,
+                _ => addr += 2,
+            }
+        }
+
+        let mut bytes = Vec::new();
+        for tokens in &lines {
+            match tokens[..] {
+                [":org", _] | [":", _] => {}
+                [first, ..] if is_number(first) => {
+                    bytes.extend(tokens.iter().map(|token| number(token) as u8))
+                }
+                ["i", ":=", "long", n] => {
+                    bytes.extend([0xF0, 0x00]);
+                    bytes.extend(target(n, &labels).to_be_bytes());
+                }
+                _ => bytes.extend(statement(tokens, &labels).encode().to_be_bytes()),
+            }
+        }
+
+        (origin, bytes)
+    }
+
+    fn number(token: &str) -> u16 {
+        u16::from_str_radix(token.strip_prefix("0x").unwrap(), 16).unwrap()
+    }
+
+    fn target(token: &str, labels: &BTreeMap<&str, u16>) -> u16 {
+        labels.get(token).copied().unwrap_or_else(|| number(token))
+    }
+
+    fn statement(tokens: &[&str], labels: &BTreeMap<&str, u16>) -> Instruction {
+        use crate::cpu::{AddType, SEType};
+
+        let v = |token: &str| u8::from_str_radix(token.strip_prefix('v').unwrap(), 16).unwrap();
+        let byte = |token: &str| number(token) as u8;
+        let operand = |token: &str| match token.starts_with('v') {
+            true => SEType::Reg(v(token)),
+            false => SEType::Byte(byte(token)),
+        };
+
+        match *tokens {
+            ["clear"] => Instruction::CLS,
+            ["return"] => Instruction::RET,
+            ["exit"] => Instruction::EXIT,
+            ["lores"] => Instruction::LOW,
+            ["hires"] => Instruction::HIGH,
+            ["scroll-right"] => Instruction::SCR,
+            ["scroll-left"] => Instruction::SCL,
+            ["audio"] => Instruction::AUDIO,
+            ["scroll-down", n] => Instruction::SCD(byte(n)),
+            ["scroll-up", n] => Instruction::SCU(byte(n)),
+            ["plane", n] => Instruction::PLANE(byte(n)),
+            ["jump", t] => Instruction::JP(JPType::Addr(target(t, labels))),
+            ["jump0", t] => Instruction::JP(JPType::FromV0(target(t, labels))),
+            ["i", ":=", "hex", x] => Instruction::LD(v(x), LDType::F),
+            ["i", ":=", "bighex", x] => Instruction::LD(v(x), LDType::HF),
+            ["i", ":=", t] => Instruction::LD(0, LDType::Addr(target(t, labels))),
+            ["i", "+=", x] => Instruction::ADD(v(x), AddType::I),
+            ["if", x, "key", "then"] => Instruction::SKNP(v(x)),
+            ["if", x, "-key", "then"] => Instruction::SKP(v(x)),
+            ["if", x, "!=", y, "then"] => Instruction::SE(v(x), operand(y)),
+            ["if", x, "==", y, "then"] => Instruction::SNE(v(x), operand(y)),
+            ["delay", ":=", x] => Instruction::LD(v(x), LDType::ToDT),
+            ["buzzer", ":=", x] => Instruction::LD(v(x), LDType::ToST),
+            ["pitch", ":=", x] => Instruction::PITCH(v(x)),
+            ["bcd", x] => Instruction::LD(v(x), LDType::B),
+            ["save", x] => Instruction::LD(v(x), LDType::ToI),
+            ["load", x] => Instruction::LD(v(x), LDType::FromI),
+            ["saveflags", x] => Instruction::LD(v(x), LDType::ToR),
+            ["loadflags", x] => Instruction::LD(v(x), LDType::FromR),
+            ["save", x, "-", y] => Instruction::LD(v(x), LDType::ToIRange(v(y))),
+            ["load", x, "-", y] => Instruction::LD(v(x), LDType::FromIRange(v(y))),
+            ["sprite", x, y, n] => Instruction::DRW(v(x), v(y), byte(n)),
+            [x, ":=", "random", n] => Instruction::RND(v(x), byte(n)),
+            [x, ":=", "delay"] => Instruction::LD(v(x), LDType::FromDT),
+            [x, ":=", "key"] => Instruction::LD(v(x), LDType::KeyPress),
+            [x, ":=", y] if y.starts_with('v') => Instruction::LD(v(x), LDType::Reg(v(y))),
+            [x, ":=", n] => Instruction::LD(v(x), LDType::Byte(byte(n))),
+            [x, "+=", y] if y.starts_with('v') => Instruction::ADD(v(x), AddType::Reg(v(y))),
+            [x, "+=", n] => Instruction::ADD(v(x), AddType::Byte(byte(n))),
+            [x, "|=", y] => Instruction::OR(v(x), v(y)),
+            [x, "&=", y] => Instruction::AND(v(x), v(y)),
+            [x, "^=", y] => Instruction::XOR(v(x), v(y)),
+            [x, "-=", y] => Instruction::SUB(v(x), v(y)),
+            [x, ">>=", y] => Instruction::SHR(v(x), v(y)),
+            [x, "=-", y] => Instruction::SUBN(v(x), v(y)),
+            [x, "<<=", y] => Instruction::SHL(v(x), v(y)),
+            // a bare name calls the subroutine
+            [name] => Instruction::CALL(labels[name]),
+            _ => panic!("not Octo this assembles: {:?}", tokens),
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use crate::cpu::History;
+
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    RomTooLarge { size: usize, max: usize },
+    // history holds the instructions that led up to it
+    IllegalOpcode { addr: u16, opcode: u16, history: History },
+    StackOverflow { addr: u16 },
+    StackUnderflow { addr: u16 },
+    MemoryOutOfBounds { addr: usize },
+    IllegalMachineCode { addr: u16, opcode: u8 },
+    MachineCodeTimeout { addr: u16 },
+    // a MegaChip instruction ran without a MegaChip buffer attached
+    MegaChipUnavailable { addr: u16 },
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(err) => write!(f, "i/o error: {}", err),
+            Self::RomTooLarge { size, max } => {
+                write!(f, "rom is {} bytes, but only {} bytes fit in memory", size, max)
+            }
+            Self::IllegalOpcode { addr, opcode, history } => {
+                write!(f, "illegal opcode {:#06x} at {:#05x}, after:{}", opcode, addr, history)
+            }
+            Self::StackOverflow { addr } => write!(f, "stack overflow at {:#05x}", addr),
+            Self::StackUnderflow { addr } => write!(f, "stack underflow at {:#05x}", addr),
+            Self::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:#05x}", addr)
+            }
+            Self::IllegalMachineCode { addr, opcode } => {
+                write!(f, "illegal 1802 opcode {:#04x} at {:#05x}", opcode, addr)
+            }
+            Self::MachineCodeTimeout { addr } => {
+                write!(f, "machine code subroutine at {:#05x} never returned", addr)
+            }
+            Self::MegaChipUnavailable { addr } => {
+                write!(f, "MegaChip instruction at {:#05x} without a MegaChip buffer", addr)
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -1 +1,19 @@
-pub mod cpu;
\ No newline at end of file
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+#[cfg(feature = "std")]
+pub mod audio;
+pub mod cdp1802;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod disasm;
+mod error;
+pub mod font;
+pub mod megachip;
+pub mod platform;
+pub mod policy;
+pub mod quirks;
+pub mod random;
+pub mod syntax;
+pub mod timing;
+
+pub use error::{Error, Result};
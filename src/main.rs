@@ -1,5 +1,6 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use muda::{Menu, MenuEvent, Submenu};
-use pixels::{Error, Pixels, SurfaceTexture};
+use pixels::{Pixels, SurfaceTexture};
 use tao::dpi::LogicalSize;
 use tao::event::{ElementState, Event, WindowEvent};
 use tao::event_loop::{ControlFlow, EventLoop};
@@ -7,28 +8,229 @@ use tao::keyboard::KeyCode;
 use tao::platform::unix::WindowExtUnix as _;
 use tao::window::{WindowBuilder};
 
-use chip8::cpu::{Cpu, Instruction};
+use chip8::audio::{OfflineRenderer, Synth, Voice};
+use chip8::cpu::Cpu;
+use chip8::platform::Platform;
+use chip8::policy::{MemoryPolicy, OpcodePolicy};
+use chip8::random::{Generator, RandomKind};
+use chip8::syntax::Syntax;
+use chip8::timing::VipTiming;
 
 use std::env;
-use std::sync::Arc;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-fn main() -> Result<(), Error> {
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// lores and hires both scale to whole pixels at this width
+const WINDOW_WIDTH: f64 = 640.0;
+const WAV_SAMPLE_RATE: u32 = 44100;
+const USAGE: &str = concat!(
+    "usage: chip8 [--platform vip|hires|chip8x|chip48|schip|xochip|megachip|modern]",
+    " [--speed N] [--vip-timing] [--memory-mapped] [--memory wrap|fault|clamp]",
+    " [--rng entropy|xorshift|vip] [--seed N] [--illegal halt|skip|trap]",
+    " [--syntax cowgod|octo|english] [--wav FILE] ROM"
+);
+
+struct Args {
+    filename: String,
+    platform: Platform,
+    instructions_per_frame: Option<usize>,
+    vip_timing: bool,
+    memory_mapped: bool,
+    memory_policy: MemoryPolicy,
+    rng: RandomKind,
+    seed: Option<u32>,
+    opcode_policy: OpcodePolicy,
+    syntax: Syntax,
+    wav: Option<String>,
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut filename = None;
+    let mut platform = Platform::default();
+    let mut instructions_per_frame = None;
+    let mut vip_timing = false;
+    let mut memory_mapped = false;
+    let mut memory_policy = MemoryPolicy::default();
+    let mut rng = RandomKind::default();
+    let mut seed = None;
+    let mut opcode_policy = OpcodePolicy::default();
+    let mut syntax = Syntax::default();
+    let mut wav = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => platform = args.next().ok_or(USAGE)?.parse()?,
+            "--speed" => instructions_per_frame = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--vip-timing" => vip_timing = true,
+            "--memory-mapped" => memory_mapped = true,
+            "--memory" => memory_policy = args.next().ok_or(USAGE)?.parse()?,
+            "--rng" => rng = args.next().ok_or(USAGE)?.parse()?,
+            "--seed" => seed = Some(args.next().ok_or(USAGE)?.parse()?),
+            "--illegal" => opcode_policy = args.next().ok_or(USAGE)?.parse()?,
+            "--syntax" => syntax = args.next().ok_or(USAGE)?.parse()?,
+            "--wav" => wav = Some(args.next().ok_or(USAGE)?),
+            _ if filename.is_none() => filename = Some(arg),
+            _ => return Err(USAGE.into()),
+        }
+    }
+
+    Ok(Args {
+        filename: filename.ok_or(USAGE)?,
+        platform,
+        instructions_per_frame,
+        vip_timing,
+        memory_mapped,
+        memory_policy,
+        rng,
+        seed,
+        opcode_policy,
+        syntax,
+        wav,
+    })
+}
+
+// the window keeps its width and follows the aspect ratio of the active
+// resolution
+fn window_size(cpu: &Cpu<Generator>) -> LogicalSize<f64> {
+    let aspect = (cpu.height as f64) / (cpu.width as f64);
+    LogicalSize::new(WINDOW_WIDTH, WINDOW_WIDTH * aspect)
+}
+
+// the hex keypad's 4x4 grid on the left of the keyboard:
+// 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F
+fn keypad_index(key: KeyCode) -> Option<usize> {
+    let keycode = match key {
+        KeyCode::Digit1 => 0x1,
+        KeyCode::Digit2 => 0x2,
+        KeyCode::Digit3 => 0x3,
+        KeyCode::Digit4 => 0xC,
+        KeyCode::KeyQ => 0x4,
+        KeyCode::KeyW => 0x5,
+        KeyCode::KeyE => 0x6,
+        KeyCode::KeyR => 0xD,
+        KeyCode::KeyA => 0x7,
+        KeyCode::KeyS => 0x8,
+        KeyCode::KeyD => 0x9,
+        KeyCode::KeyF => 0xE,
+        KeyCode::KeyZ => 0xA,
+        KeyCode::KeyX => 0x0,
+        KeyCode::KeyC => 0xB,
+        KeyCode::KeyV => 0xF,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+// the same grid on the numpad, with its digits where the keypad has them
+fn numpad_index(key: KeyCode) -> Option<usize> {
+    let keycode = match key {
+        KeyCode::Numpad7 => 0x1,
+        KeyCode::Numpad8 => 0x2,
+        KeyCode::Numpad9 => 0x3,
+        KeyCode::NumpadDivide => 0xC,
+        KeyCode::Numpad4 => 0x4,
+        KeyCode::Numpad5 => 0x5,
+        KeyCode::Numpad6 => 0x6,
+        KeyCode::NumpadMultiply => 0xD,
+        KeyCode::Numpad1 => 0x7,
+        KeyCode::Numpad2 => 0x8,
+        KeyCode::Numpad3 => 0x9,
+        KeyCode::NumpadSubtract => 0xE,
+        KeyCode::NumpadDecimal => 0xA,
+        KeyCode::Numpad0 => 0x0,
+        KeyCode::NumpadEnter => 0xB,
+        KeyCode::NumpadAdd => 0xF,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+// plays whatever voice the emulator last published, until the stream is dropped
+fn start_audio(voice: Arc<Mutex<Voice>>) -> Result<cpal::Stream, Box<dyn Error>> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("no audio output device")?;
+    let config = device.default_output_config()?;
+    if config.sample_format() != cpal::SampleFormat::F32 {
+        return Err("audio output device does not take f32 samples".into());
+    }
+
+    let channels = config.channels() as usize;
+    let mut synth = Synth::new(config.sample_rate().0);
+    let mut current = Voice::default();
+    let stream = device.build_output_stream(
+        &config.into(),
+        move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+            // never wait on the frame loop here, if it is publishing a voice
+            // right now keep playing the last one
+            if let Ok(voice) = voice.try_lock() {
+                current.clone_from(&voice);
+            }
+            // nothing may allocate in here: the mono samples go in the front
+            // of `data`, then are spread over every channel back to front so
+            // none is overwritten before it is read
+            let frames = data.len() / channels;
+            synth.render(&current, &mut data[..frames]);
+            for frame in (0..frames).rev() {
+                let sample = data[frame];
+                data[frame * channels..(frame + 1) * channels].fill(sample);
+            }
+        },
+        |err| println!("audio error: {}", err),
+        None,
+    )?;
+    stream.play()?;
+
+    Ok(stream)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // skipped illegal opcodes are reported as warnings
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
     println!("Hello, CHIP-8!");
-    let filename: String = env::args()
-        .nth(1)
-        .expect("Expected a single command line argument");
+    let args = parse_args()?;
+    let filename = args.filename;
+    let instructions_per_frame = args
+        .instructions_per_frame
+        .unwrap_or(args.platform.instructions_per_frame());
 
-    println!("Initializing CPU...");
-    let mut cpu = Cpu::init();
+    println!("Initializing CPU for {}...", args.platform);
+    let rng = Generator::new(args.rng, args.seed);
+    let mut cpu = Cpu::with_platform(args.platform, rng);
+    if args.vip_timing {
+        cpu.timing = Some(VipTiming::default());
+    }
+    cpu.memory_mapped = args.memory_mapped;
+    cpu.memory_policy = args.memory_policy;
+    cpu.opcode_policy = args.opcode_policy;
+    cpu.syntax = args.syntax;
 
     println!("Loading rom...");
-    cpu.load_rom(&filename);
+    cpu.load_rom_from_path(&filename)?;
     // cpu.print_ram();
 
+    // the frame loop updates its own voice and only locks to publish a copy
+    let mut voice = cpu.voice();
+    let published = Arc::new(Mutex::new(voice.clone()));
+    // sound is optional, keep emulating without it
+    let stream = match start_audio(Arc::clone(&published)) {
+        Ok(stream) => Some(stream),
+        Err(err) => {
+            println!("Audio disabled: {}", err);
+            None
+        }
+    };
+    let mut recorder = args.wav.map(|path| (path, OfflineRenderer::new(WAV_SAMPLE_RATE)));
+
     println!("Rendering display window...");
     let event_loop = EventLoop::new();
     let window = {
-        let size = LogicalSize::new((cpu.width as f64)*5.0, (cpu.height as f64)*5.0);
+        let size = window_size(&cpu);
         let window = WindowBuilder::new()
             .with_title("CHIP-8 shenanigans")
             .with_inner_size(size)
@@ -55,7 +257,15 @@ fn main() -> Result<(), Error> {
     menu.init_for_gtk_window(window.gtk_window(), window.default_vbox())
         .unwrap();
 
+    let mut next_frame = Instant::now();
+    // set once the rom hits an illegal opcode under --illegal trap, the
+    // machine stays frozen on it for inspection
+    let mut trapped = false;
+
     event_loop.run(move |event, _, control_flow| {
+        // keep the output stream alive for as long as the event loop
+        let _ = &stream;
+
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => {
@@ -69,37 +279,27 @@ fn main() -> Result<(), Error> {
                         cpu.dump_state();
                         *control_flow = ControlFlow::Exit;
                     } else {
-                        let keycode = match key {
-                            KeyCode::Digit1 => 0x1,
-                            KeyCode::Digit2 => 0x2,
-                            KeyCode::Digit3 => 0x3,
-                            KeyCode::Digit4 => 0xC,
-                            KeyCode::KeyQ => 0x4,
-                            KeyCode::KeyW => 0x5,
-                            KeyCode::KeyE => 0x6,
-                            KeyCode::KeyR => 0xD,
-                            KeyCode::KeyA => 0x7,
-                            KeyCode::KeyS => 0x8,
-                            KeyCode::KeyD => 0x9,
-                            KeyCode::KeyF => 0xE,
-                            KeyCode::KeyZ => 0xA,
-                            KeyCode::KeyX => 0x0,
-                            KeyCode::KeyC => 0xB,
-                            KeyCode::KeyV => 0xF,
-                            _ => todo!(),
+                        // CHIP-8X's second keypad is on the numpad
+                        let second = cpu.platform.supports_chip8x();
+                        let (keypad, keycode) = match (keypad_index(key), numpad_index(key)) {
+                            (Some(keycode), _) => (&mut cpu.kp, keycode),
+                            (None, Some(keycode)) if second => (&mut cpu.kp2, keycode),
+                            // any other key does nothing
+                            _ => return,
                         };
                         let state = match inner.state {
                             ElementState::Pressed => true,
                             ElementState::Released => false,
                             _ => unreachable!(),
                         };
-                        cpu.kp[keycode] = state;
+                        keypad[keycode] = state;
                         *control_flow = ControlFlow::Poll;
                     }
                 }
 
                 WindowEvent::Resized(size) => {
-                    if pixels.resize_surface(size.width, size.height).is_err() {
+                    let resized = pixels.resize_surface(size.width, size.height);
+                    if resized.is_err() {
                         *control_flow = ControlFlow::Exit;
                     }
                 }
@@ -110,45 +310,60 @@ fn main() -> Result<(), Error> {
             }
 
             Event::MainEventsCleared => {
-                if cpu.pc as usize >= cpu.ram.len() {
-                    println!("reached end of ram.");
-                    cpu.dump_state();
-                    std::process::exit(0x0100);
-                }
-
-                let bytes = cpu.fetch();
-
-                if bytes == 0 {
-                    println!("hit raw 0x0000");
-                    cpu.dump_state();
-                    std::process::exit(0x0100);
-                }
-
-                let instruction = Instruction::decode(bytes).unwrap();
-                // println!("{}", instruction.to_string());
-                let result = cpu.execute(instruction);
-
-                if result.is_some() {
-                    match result.unwrap() {
-                        1 => window.request_redraw(),
-                        2 => *control_flow = ControlFlow::Wait,
-                        _ => {
-                            println!("unknown return value from cpu.execute(), aborting...");
+                let now = Instant::now();
+                if now >= next_frame && !trapped {
+                    let frame = match cpu.run_frame(instructions_per_frame) {
+                        Ok(frame) => frame,
+                        Err(err) => {
+                            println!("emulation stopped: {}", err);
                             cpu.dump_state();
-                            std::process::exit(0x0100);
+                            *control_flow = ControlFlow::Exit;
+                            return;
                         }
                     };
-                }
 
-                if cpu.dt > 0 {
-                    cpu.dt -= 1;
-                }
+                    if let Some(opcode) = frame.trapped {
+                        println!(
+                            "trapped on illegal opcode {:#06x} at {:#05x}, after:{}",
+                            opcode,
+                            cpu.pc,
+                            cpu.history().display(cpu.syntax)
+                        );
+                        cpu.dump_state();
+                        trapped = true;
+                    }
+
+                    if frame.resolution_changed {
+                        let resized = pixels.resize_buffer(cpu.width as u32, cpu.height as u32);
+                        if let Err(err) = resized {
+                            println!("{}", err);
+                            *control_flow = ControlFlow::Exit;
+                            return;
+                        }
+                        let size = window_size(&cpu);
+                        window.set_min_inner_size(Some(size));
+                        window.set_inner_size(size);
+                    }
 
-                if cpu.st > 0 {
-                    cpu.st -= 1;
+                    if frame.display_changed {
+                        window.request_redraw();
+                    }
+
+                    voice.update(&cpu);
+                    published.lock().unwrap().clone_from(&voice);
+                    if let Some((_, recorder)) = &mut recorder {
+                        recorder.record_frame(&voice);
+                    }
+
+                    // if the host fell far behind (e.g. the window was being
+                    // dragged), resync instead of running a burst of frames
+                    next_frame += FRAME_DURATION;
+                    if now > next_frame + FRAME_DURATION * 4 {
+                        next_frame = now + FRAME_DURATION;
+                    }
                 }
 
-                *control_flow = ControlFlow::Poll;
+                *control_flow = ControlFlow::WaitUntil(next_frame);
             }
 
             Event::RedrawRequested(_) => {
@@ -159,14 +374,24 @@ fn main() -> Result<(), Error> {
                 }
             }
 
-            _ => {
-                if let Ok(event) = MenuEvent::receiver().try_recv() {
-                    if event.id.0 == "quit" {
-                        cpu.dump_state();
-                        *control_flow = ControlFlow::Exit;
+            Event::LoopDestroyed => {
+                if let Some((path, recorder)) = &recorder {
+                    let written = File::create(path)
+                        .and_then(|file| recorder.write_wav(BufWriter::new(file)));
+                    if let Err(err) = written {
+                        println!("could not write {}: {}", path, err);
                     }
                 }
             }
+
+            _ => {
+                if let Ok(event) = MenuEvent::receiver().try_recv()
+                    && event.id.0 == "quit"
+                {
+                    cpu.dump_state();
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
         }
     });
 }
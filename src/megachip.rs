@@ -0,0 +1,230 @@
+// the MegaChip 256x192 mode: sprites are palette indices drawn straight into
+// an rgba framebuffer, blended with what's already there
+pub const WIDTH: usize = 256;
+pub const HEIGHT: usize = 192;
+
+const OPAQUE_BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+// how a sprite pixel is combined with the framebuffer, set by 080N
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Alpha25,
+    Alpha50,
+    Add,
+    Multiply,
+}
+
+impl BlendMode {
+    // unknown modes draw normally
+    pub fn from_n(n: u8) -> BlendMode {
+        match n {
+            1 => BlendMode::Alpha25,
+            2 => BlendMode::Alpha50,
+            3 => BlendMode::Add,
+            4 => BlendMode::Multiply,
+            _ => BlendMode::Normal,
+        }
+    }
+
+    pub fn blend(&self, dst: [u8; 4], src: [u8; 4]) -> [u8; 4] {
+        let mix = |f: fn(u16, u16) -> u16| -> [u8; 4] {
+            let mut out = OPAQUE_BLACK;
+            for c in 0..3 {
+                out[c] = f(dst[c] as u16, src[c] as u16).min(0xFF) as u8;
+            }
+            out
+        };
+
+        match self {
+            BlendMode::Normal => src,
+            BlendMode::Alpha25 => mix(|dst, src| (dst * 3 + src) / 4),
+            BlendMode::Alpha50 => mix(|dst, src| (dst + src) / 2),
+            BlendMode::Add => mix(|dst, src| dst + src),
+            BlendMode::Multiply => mix(|dst, src| dst * src / 0xFF),
+        }
+    }
+}
+
+// an 8-bit unsigned pcm sample started by 060N. `id` changes every time a
+// sample is started, so players can tell a restart from the same sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub addr: usize,
+    pub len: usize,
+    pub rate: u16,
+    pub looping: bool,
+    pub id: u32,
+}
+
+// a Cpu only holds the ~245K of MegaChip state on that platform. under std
+// it is boxed, without std the caller lends a buffer, e.g. from a static
+#[cfg(feature = "std")]
+pub type Buffer = Box<MegaChip>;
+#[cfg(not(feature = "std"))]
+pub type Buffer = &'static mut MegaChip;
+
+pub struct MegaChip {
+    pub enabled: bool, // 0011 / 0010
+    pub framebuffer: [[u8; 4]; WIDTH * HEIGHT],
+    // palette index last drawn at each pixel, for collisions
+    pub indices: [u8; WIDTH * HEIGHT],
+    // rgba, index 0 is transparent and never drawn
+    pub palette: [[u8; 4]; 256],
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    pub alpha: u8, // the whole screen fades to black with it
+    pub blend: BlendMode,
+    pub collision_index: u8,
+    pub sample: Option<Sample>,
+    samples_started: u32,
+}
+
+impl Default for MegaChip {
+    fn default() -> Self {
+        MegaChip::new()
+    }
+}
+
+impl MegaChip {
+    pub const fn new() -> MegaChip {
+        MegaChip {
+            enabled: false,
+            framebuffer: [OPAQUE_BLACK; WIDTH * HEIGHT],
+            indices: [0; WIDTH * HEIGHT],
+            palette: [OPAQUE_BLACK; 256],
+            sprite_width: 0,
+            sprite_height: 0,
+            alpha: 0xFF,
+            blend: BlendMode::Normal,
+            collision_index: 0,
+            sample: None,
+            samples_started: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.framebuffer.fill(OPAQUE_BLACK);
+        self.indices.fill(0);
+    }
+
+    pub fn start_sample(&mut self, addr: usize, len: usize, rate: u16, looping: bool) {
+        self.samples_started = self.samples_started.wrapping_add(1);
+        self.sample = Some(Sample {
+            addr,
+            len,
+            rate,
+            looping,
+            id: self.samples_started,
+        });
+    }
+
+    // moves the framebuffer by (dx, dy), filling the gap with black
+    pub fn scroll(&mut self, dx: isize, dy: isize) {
+        shift(&mut self.framebuffer, dx, dy, OPAQUE_BLACK);
+        shift(&mut self.indices, dx, dy, 0);
+    }
+
+    pub fn draw(&self, frame: &mut [u8]) {
+        let alpha = self.alpha as u16;
+        for (pixel, rgba) in frame.chunks_exact_mut(4).zip(&self.framebuffer) {
+            for c in 0..3 {
+                pixel[c] = (rgba[c] as u16 * alpha / 0xFF) as u8;
+            }
+            pixel[3] = 0xFF;
+        }
+    }
+}
+
+// moves a WIDTH x HEIGHT buffer by (dx, dy) in place. rows are visited
+// against the direction of travel, so every source row is read before it is
+// written over
+fn shift<T: Copy>(buffer: &mut [T], dx: isize, dy: isize, fill: T) {
+    let cols = WIDTH.saturating_sub(dx.unsigned_abs());
+    let (from, to) = if dx >= 0 { (0, WIDTH - cols) } else { (WIDTH - cols, 0) };
+
+    for i in 0..HEIGHT {
+        let row = if dy > 0 { HEIGHT - 1 - i } else { i };
+        let start = row * WIDTH;
+        let src_row = row as isize - dy;
+
+        if (0..HEIGHT as isize).contains(&src_row) && cols > 0 {
+            let src = src_row as usize * WIDTH;
+            buffer.copy_within(src + from..src + from + cols, start + to);
+            // the columns the row moved away from
+            let gap = if dx >= 0 { start..start + to } else { start + cols..start + WIDTH };
+            buffer[gap].fill(fill);
+        } else {
+            buffer[start..start + WIDTH].fill(fill);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_modes() {
+        let dst = [0x40, 0x80, 0xFF, 0xFF];
+        let src = [0xC0, 0x80, 0x00, 0xFF];
+
+        assert_eq!(BlendMode::Normal.blend(dst, src), src);
+        assert_eq!(BlendMode::Alpha25.blend(dst, src), [0x60, 0x80, 0xBF, 0xFF]);
+        assert_eq!(BlendMode::Alpha50.blend(dst, src), [0x80, 0x80, 0x7F, 0xFF]);
+        assert_eq!(BlendMode::Add.blend(dst, src), [0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(
+            BlendMode::Multiply.blend(dst, src),
+            [0x30, 0x40, 0x00, 0xFF]
+        );
+        assert_eq!(BlendMode::from_n(9), BlendMode::Normal);
+    }
+
+    #[test]
+    fn test_scroll_and_fade() {
+        let mut mega = MegaChip::new();
+        mega.framebuffer[0] = [0xFF, 0x80, 0x00, 0xFF];
+        mega.indices[0] = 7;
+
+        mega.scroll(1, 2);
+
+        let idx = 2 * WIDTH + 1;
+        assert_eq!(mega.framebuffer[idx], [0xFF, 0x80, 0x00, 0xFF]);
+        assert_eq!(mega.indices[idx], 7);
+        assert_eq!(mega.framebuffer[0], OPAQUE_BLACK);
+
+        mega.alpha = 0x80;
+        let mut frame = vec![0; WIDTH * HEIGHT * 4];
+        mega.draw(&mut frame);
+
+        assert_eq!(frame[idx * 4..idx * 4 + 4], [0x80, 0x40, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn test_scroll_every_direction() {
+        for (dx, dy) in [(4, 0), (-4, 0), (0, 3), (0, -3), (-2, 5), (WIDTH as isize, 0)] {
+            let mut mega = MegaChip::new();
+            for (idx, index) in mega.indices.iter_mut().enumerate() {
+                *index = (idx % 251) as u8;
+            }
+            let before = mega.indices;
+
+            mega.scroll(dx, dy);
+
+            for row in 0..HEIGHT {
+                for col in 0..WIDTH {
+                    let (src_row, src_col) = (row as isize - dy, col as isize - dx);
+                    let expected = if (0..HEIGHT as isize).contains(&src_row)
+                        && (0..WIDTH as isize).contains(&src_col)
+                    {
+                        before[src_row as usize * WIDTH + src_col as usize]
+                    } else {
+                        0
+                    };
+                    assert_eq!(mega.indices[row * WIDTH + col], expected, "({}, {})", dx, dy);
+                }
+            }
+        }
+    }
+}
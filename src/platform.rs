@@ -0,0 +1,260 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::font::{BigFont, SmallFont};
+use crate::quirks::Quirks;
+
+// the largest address space any supported platform has (XO-CHIP)
+pub const MAX_MEMORY: usize = 0x10000;
+
+// without std the stack is a fixed array, so even platforms with no limit
+// can only nest this many calls
+pub const MAX_STACK_DEPTH: usize = 255;
+
+// the largest display any supported platform has (SUPER-CHIP hires)
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
+
+// the historical interpreters, each bundling the memory size, display,
+// entry point, font, quirks and speed a rom written for it expects
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    CosmacVip,
+    HiRes,
+    Chip8X,
+    Chip48,
+    SuperChip,
+    XoChip,
+    MegaChip,
+    #[default]
+    Modern,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 8] = [
+        Platform::CosmacVip,
+        Platform::HiRes,
+        Platform::Chip8X,
+        Platform::Chip48,
+        Platform::SuperChip,
+        Platform::XoChip,
+        Platform::MegaChip,
+        Platform::Modern,
+    ];
+
+    // short name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CosmacVip => "vip",
+            Self::HiRes => "hires",
+            Self::Chip8X => "chip8x",
+            Self::Chip48 => "chip48",
+            Self::SuperChip => "schip",
+            Self::XoChip => "xochip",
+            Self::MegaChip => "megachip",
+            Self::Modern => "modern",
+        }
+    }
+
+    pub fn memory_size(&self) -> usize {
+        match self {
+            // MegaChip addresses 16MB, only the first 64K are backed here
+            Self::XoChip | Self::MegaChip => MAX_MEMORY,
+            _ => 0x1000,
+        }
+    }
+
+    // (width, height) of the display at startup
+    pub fn resolution(&self) -> (u16, u16) {
+        match self {
+            Self::HiRes => (64, 64),
+            _ => (64, 32),
+        }
+    }
+
+    pub fn start_addr(&self) -> u16 {
+        match self {
+            // the colour interpreter doesn't fit in the usual 512 bytes
+            Self::Chip8X => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    // where execution begins once `rom` is loaded. HI-RES CHIP-8 roms open
+    // with `1260`, a jump into the two-page interpreter patch, and the
+    // program proper starts at 0x2C0
+    pub fn entry_point(&self, rom: &[u8]) -> u16 {
+        match self {
+            Self::HiRes if rom.starts_with(&[0x12, 0x60]) => 0x2C0,
+            _ => self.start_addr(),
+        }
+    }
+
+    pub fn font(&self) -> SmallFont {
+        match self {
+            Self::CosmacVip | Self::HiRes | Self::Chip8X => SmallFont::Vip,
+            Self::Chip48 | Self::SuperChip | Self::MegaChip => SmallFont::Chip48,
+            Self::XoChip | Self::Modern => SmallFont::Octo,
+        }
+    }
+
+    pub fn big_font(&self) -> Option<BigFont> {
+        match self {
+            Self::SuperChip | Self::MegaChip => Some(BigFont::SuperChip),
+            Self::XoChip => Some(BigFont::XoChip),
+            _ => None,
+        }
+    }
+
+    // where the small font is loaded and FX29 points. the VIP family keeps
+    // it in the interpreter's own area at the bottom of memory, the rest
+    // use the customary 0x050
+    pub fn font_addr(&self) -> u16 {
+        match self {
+            Self::CosmacVip | Self::HiRes | Self::Chip8X => 0x000,
+            _ => 0x050,
+        }
+    }
+
+    // the big font follows the small one
+    pub fn big_font_addr(&self) -> u16 {
+        self.font_addr() + 80
+    }
+
+    // scrolling, hires, 16x16 sprites, the big font and the rpl flags
+    pub fn supports_superchip(&self) -> bool {
+        matches!(self, Self::SuperChip | Self::XoChip | Self::MegaChip)
+    }
+
+    // long I loads, bitplanes, register ranges and scrolling up
+    pub fn supports_xochip(&self) -> bool {
+        matches!(self, Self::XoChip)
+    }
+
+    // the 256x192 palette mode, sprite size, blending and sampled sound
+    pub fn supports_megachip(&self) -> bool {
+        matches!(self, Self::MegaChip)
+    }
+
+    // 0NNN runs 1802 machine code against the VIP memory layout
+    pub fn supports_machine_code(&self) -> bool {
+        matches!(self, Self::CosmacVip)
+    }
+
+    // the two-page 64x64 display, cleared by 0230
+    pub fn supports_two_page(&self) -> bool {
+        matches!(self, Self::HiRes)
+    }
+
+    // the VP-590 colour board: background and foreground colour zones, and
+    // the second keypad
+    pub fn supports_chip8x(&self) -> bool {
+        matches!(self, Self::Chip8X)
+    }
+
+    // how many nested calls the interpreter had room for, None when modern
+    // interpreters don't limit it
+    pub fn stack_depth(&self) -> Option<usize> {
+        match self {
+            Self::CosmacVip | Self::HiRes | Self::Chip8X => Some(12),
+            Self::Chip48 | Self::SuperChip | Self::XoChip | Self::MegaChip => Some(16),
+            Self::Modern => None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Self::CosmacVip | Self::HiRes | Self::Chip8X => Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                vf_reset: true,
+                clip_sprites: true,
+                display_wait: true,
+            },
+            Self::Chip48 | Self::SuperChip | Self::MegaChip => Quirks {
+                shift_uses_vy: false,
+                load_store_increments_i: false,
+                jump_uses_vx: true,
+                vf_reset: false,
+                clip_sprites: true,
+                display_wait: false,
+            },
+            Self::XoChip => Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                jump_uses_vx: false,
+                vf_reset: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+            Self::Modern => Quirks::default(),
+        }
+    }
+
+    // roughly how fast the original ran, in instructions per 60 Hz frame
+    pub fn instructions_per_frame(&self) -> usize {
+        match self {
+            Self::CosmacVip | Self::HiRes | Self::Chip8X => 15,
+            Self::Chip48 | Self::SuperChip => 30,
+            Self::XoChip | Self::MegaChip => 1000,
+            Self::Modern => 10,
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownPlatform;
+
+impl fmt::Display for UnknownPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown platform, expected one of vip, hires, chip8x, chip48, schip, xochip, megachip, modern")
+    }
+}
+
+impl core::error::Error for UnknownPlatform {}
+
+impl FromStr for Platform {
+    type Err = UnknownPlatform;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Platform::ALL
+            .into_iter()
+            .find(|platform| platform.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownPlatform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for platform in Platform::ALL {
+            assert_eq!(platform.name().parse::<Platform>(), Ok(platform));
+        }
+
+        assert_eq!("VIP".parse::<Platform>(), Ok(Platform::CosmacVip));
+        assert_eq!("gameboy".parse::<Platform>(), Err(UnknownPlatform));
+    }
+
+    #[test]
+    fn test_modern_matches_defaults() {
+        assert_eq!(Platform::Modern.quirks(), Quirks::default());
+        assert_eq!(Platform::Modern.memory_size(), 0x1000);
+    }
+
+    #[test]
+    fn test_hires_entry_point() {
+        assert_eq!(Platform::HiRes.entry_point(&[0x12, 0x60, 0x00, 0xE0]), 0x2C0);
+        assert_eq!(Platform::HiRes.entry_point(&[0x00, 0xE0]), 0x200);
+        assert_eq!(Platform::CosmacVip.entry_point(&[0x12, 0x60]), 0x200);
+    }
+}
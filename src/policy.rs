@@ -0,0 +1,150 @@
+use core::fmt;
+use core::str::FromStr;
+
+// what happens when a program fetches from, or reads or writes through I,
+// past the end of memory
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPolicy {
+    // addresses wrap around to the start of memory, like the VIP's 4 KiB
+    // address decoding
+    Wrap,
+    // the access fails with Error::MemoryOutOfBounds and nothing is changed
+    #[default]
+    Fault,
+    // addresses past the end all land on the last byte of memory
+    Clamp,
+}
+
+impl MemoryPolicy {
+    pub const ALL: [MemoryPolicy; 3] =
+        [MemoryPolicy::Wrap, MemoryPolicy::Fault, MemoryPolicy::Clamp];
+
+    // short name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Wrap => "wrap",
+            Self::Fault => "fault",
+            Self::Clamp => "clamp",
+        }
+    }
+
+    // where the byte at `addr` lives in `size` bytes of memory. under Fault
+    // the address is returned unchanged, the caller checks it
+    pub fn resolve(&self, addr: usize, size: usize) -> usize {
+        match self {
+            Self::Wrap => addr % size,
+            Self::Fault => addr,
+            Self::Clamp => addr.min(size - 1),
+        }
+    }
+}
+
+impl fmt::Display for MemoryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownMemoryPolicy;
+
+impl fmt::Display for UnknownMemoryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown memory policy, expected one of wrap, fault, clamp")
+    }
+}
+
+impl core::error::Error for UnknownMemoryPolicy {}
+
+impl FromStr for MemoryPolicy {
+    type Err = UnknownMemoryPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        MemoryPolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownMemoryPolicy)
+    }
+}
+
+// what happens when the program counter reaches an opcode the platform
+// doesn't decode, including a raw 0x0000
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OpcodePolicy {
+    // stop with Error::IllegalOpcode
+    #[default]
+    Halt,
+    // log it and carry on with the next instruction
+    Skip,
+    // stop before it with StepOutcome::Trapped, so a debugger can look at
+    // the machine and carry on from there
+    Trap,
+}
+
+impl OpcodePolicy {
+    pub const ALL: [OpcodePolicy; 3] = [OpcodePolicy::Halt, OpcodePolicy::Skip, OpcodePolicy::Trap];
+
+    // short name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Halt => "halt",
+            Self::Skip => "skip",
+            Self::Trap => "trap",
+        }
+    }
+}
+
+impl fmt::Display for OpcodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpcodePolicy;
+
+impl fmt::Display for UnknownOpcodePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown illegal opcode policy, expected one of halt, skip, trap")
+    }
+}
+
+impl core::error::Error for UnknownOpcodePolicy {}
+
+impl FromStr for OpcodePolicy {
+    type Err = UnknownOpcodePolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OpcodePolicy::ALL
+            .into_iter()
+            .find(|policy| policy.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownOpcodePolicy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for policy in MemoryPolicy::ALL {
+            assert_eq!(policy.name().parse::<MemoryPolicy>(), Ok(policy));
+        }
+        for policy in OpcodePolicy::ALL {
+            assert_eq!(policy.name().parse::<OpcodePolicy>(), Ok(policy));
+        }
+        assert_eq!("ignore".parse::<MemoryPolicy>(), Err(UnknownMemoryPolicy));
+        assert_eq!("ignore".parse::<OpcodePolicy>(), Err(UnknownOpcodePolicy));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(MemoryPolicy::Wrap.resolve(0x1002, 0x1000), 0x002);
+        assert_eq!(MemoryPolicy::Fault.resolve(0x1002, 0x1000), 0x1002);
+        assert_eq!(MemoryPolicy::Clamp.resolve(0x1002, 0x1000), 0xFFF);
+        for policy in MemoryPolicy::ALL {
+            assert_eq!(policy.resolve(0xFFF, 0x1000), 0xFFF);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+// behaviours that differ between the historical interpreters. the default
+// is the "modern" interpretation most emulators and test roms assume, with
+// every switch off
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VY into VX (COSMAC VIP) instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing past the last register (I += X + 1)
+    pub load_store_increments_i: bool,
+    // BXNN jumps to XNN + VX instead of NNN + V0 (CHIP-48, SUPER-CHIP)
+    pub jump_uses_vx: bool,
+    // 8XY1/8XY2/8XY3 clear VF as a side effect (COSMAC VIP)
+    pub vf_reset: bool,
+    // sprites are cut off at the screen edge instead of wrapping around
+    pub clip_sprites: bool,
+    // DXYN waits for the next vertical blank, so at most one sprite is
+    // drawn per 60 Hz frame (COSMAC VIP)
+    pub display_wait: bool,
+}
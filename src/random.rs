@@ -0,0 +1,287 @@
+use core::fmt;
+use core::str::FromStr;
+
+// source of the random bytes behind RND. anything that can hand out bytes
+// works, including a plain closure, so hosts without an os entropy source
+// can inject their own
+pub trait RandomSource {
+    fn random_byte(&mut self) -> u8;
+}
+
+impl<F: FnMut() -> u8> RandomSource for F {
+    fn random_byte(&mut self) -> u8 {
+        self()
+    }
+}
+
+// xorshift32: no allocation, no os support, and plenty for games
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    pub const DEFAULT_SEED: u32 = 0x2545_F491;
+
+    pub fn new(seed: u32) -> XorShift {
+        // an all-zero state would only ever produce zeros
+        let state = if seed == 0 { Self::DEFAULT_SEED } else { seed };
+        XorShift { state }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_entropy() -> XorShift {
+        XorShift::new(rand::random())
+    }
+}
+
+impl Default for XorShift {
+    fn default() -> Self {
+        XorShift::new(Self::DEFAULT_SEED)
+    }
+}
+
+impl RandomSource for XorShift {
+    fn random_byte(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        (x >> 24) as u8
+    }
+}
+
+// a fresh byte from the os for every RND, never reproducible
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct OsEntropy;
+
+#[cfg(feature = "std")]
+impl RandomSource for OsEntropy {
+    fn random_byte(&mut self) -> u8 {
+        rand::random()
+    }
+}
+
+// the COSMAC VIP interpreter's RND. R9 is bumped, and the byte at 0x100 +
+// R9.0 (the interpreter's own code) is added to R9.1. that sum is rotated
+// right through the carry and added to itself, and the result is both the
+// new R9.1 and the random byte
+#[derive(Debug, Clone, Default)]
+pub struct VipRandom {
+    pub r9: u16,
+}
+
+impl VipRandom {
+    // page one of the VIP's CHIP-8 interpreter, 0x100-0x1FF. no platform
+    // loads the interpreter into emulated ram, so the table lives here
+    pub const PAGE_ONE: [u8; 256] = [
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+        0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+        0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+        0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+        0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+        0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+        0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+        0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+        0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+        0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+        0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+        0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+        0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+        0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+        0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+        0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xE0, 0x00, 0x4B,
+    ];
+
+    pub fn new(seed: u16) -> VipRandom {
+        VipRandom { r9: seed }
+    }
+}
+
+impl RandomSource for VipRandom {
+    fn random_byte(&mut self) -> u8 {
+        self.r9 = self.r9.wrapping_add(1);
+        let [hi, lo] = self.r9.to_be_bytes();
+        let (sum, carry) = Self::PAGE_ONE[lo as usize].overflowing_add(hi);
+        // SHRC shifts the carry in at the top
+        let byte = ((sum >> 1) | ((carry as u8) << 7)).wrapping_add(sum);
+        self.r9 = u16::from_be_bytes([byte, lo]);
+
+        byte
+    }
+}
+
+// the generators that can be picked at runtime, e.g. on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RandomKind {
+    #[cfg(feature = "std")]
+    Entropy,
+    #[default]
+    XorShift,
+    Vip,
+}
+
+impl RandomKind {
+    #[cfg(feature = "std")]
+    pub const ALL: [RandomKind; 3] = [RandomKind::Entropy, RandomKind::XorShift, RandomKind::Vip];
+    #[cfg(not(feature = "std"))]
+    pub const ALL: [RandomKind; 2] = [RandomKind::XorShift, RandomKind::Vip];
+
+    // short name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Entropy => "entropy",
+            Self::XorShift => "xorshift",
+            Self::Vip => "vip",
+        }
+    }
+}
+
+impl fmt::Display for RandomKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownRandomKind;
+
+impl fmt::Display for UnknownRandomKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown random source, expected one of entropy, xorshift, vip")
+    }
+}
+
+impl core::error::Error for UnknownRandomKind {}
+
+impl FromStr for RandomKind {
+    type Err = UnknownRandomKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RandomKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownRandomKind)
+    }
+}
+
+// one of the RandomKind generators, so the choice can be made at runtime
+#[derive(Debug, Clone)]
+pub enum Generator {
+    #[cfg(feature = "std")]
+    Entropy(OsEntropy),
+    XorShift(XorShift),
+    Vip(VipRandom),
+}
+
+impl Generator {
+    // the same kind and seed always give the same sequence. without a seed
+    // it comes from os entropy under std, otherwise the default seed is used
+    pub fn new(kind: RandomKind, seed: Option<u32>) -> Generator {
+        #[cfg(feature = "std")]
+        let seed = seed.unwrap_or_else(rand::random);
+        #[cfg(not(feature = "std"))]
+        let seed = seed.unwrap_or(XorShift::DEFAULT_SEED);
+
+        match kind {
+            #[cfg(feature = "std")]
+            RandomKind::Entropy => Generator::Entropy(OsEntropy),
+            RandomKind::XorShift => Generator::XorShift(XorShift::new(seed)),
+            RandomKind::Vip => Generator::Vip(VipRandom::new(seed as u16)),
+        }
+    }
+}
+
+impl RandomSource for Generator {
+    fn random_byte(&mut self) -> u8 {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Entropy(rng) => rng.random_byte(),
+            Self::XorShift(rng) => rng.random_byte(),
+            Self::Vip(rng) => rng.random_byte(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cdp1802::Cdp1802;
+
+    #[test]
+    fn test_xorshift_is_deterministic() {
+        let mut a = XorShift::new(1234);
+        let mut b = XorShift::new(1234);
+
+        for _ in 0..64 {
+            assert_eq!(a.random_byte(), b.random_byte());
+        }
+    }
+
+    #[test]
+    fn test_xorshift_zero_seed() {
+        let mut rng = XorShift::new(0);
+
+        assert!((0..64).any(|_| rng.random_byte() != 0));
+    }
+
+    #[test]
+    fn test_closure_source() {
+        let mut rng = || 0xAB;
+
+        assert_eq!(rng.random_byte(), 0xAB);
+    }
+
+    #[test]
+    fn test_vip_random() {
+        let mut rng = VipRandom::new(0x1234);
+
+        let bytes: Vec<u8> = (0..8).map(|_| rng.random_byte()).collect();
+        assert_eq!(bytes, [0x39, 0x32, 0x68, 0x10, 0x40, 0x65, 0xD6, 0x68]);
+        assert_eq!(rng.r9, 0x683C);
+    }
+
+    #[test]
+    fn test_vip_random_matches_interpreter() {
+        // run the interpreter's own CXKK routine at 0x1D9 with kk = 0xFF
+        let mut ram = [0u8; 0x1000];
+        ram[0x100..0x200].copy_from_slice(&VipRandom::PAGE_ONE);
+        ram[0x300] = 0xFF;
+        let mut cdp = Cdp1802::new();
+        cdp.r[9] = 0xBEEF;
+        let mut rng = VipRandom::new(0xBEEF);
+
+        for _ in 0..512 {
+            (cdp.p, cdp.x, cdp.r[3], cdp.r[5], cdp.r[6]) = (3, 2, 0x1D9, 0x300, 0xEF0);
+            while cdp.p == 3 {
+                cdp.step(&mut ram).unwrap();
+            }
+            assert_eq!(rng.random_byte(), ram[0xEF0]);
+            assert_eq!(rng.r9, cdp.r[9]);
+        }
+    }
+
+    #[test]
+    fn test_generator_is_reproducible() {
+        for kind in [RandomKind::XorShift, RandomKind::Vip] {
+            let mut a = Generator::new(kind, Some(99));
+            let mut b = Generator::new(kind, Some(99));
+
+            for _ in 0..64 {
+                assert_eq!(a.random_byte(), b.random_byte());
+            }
+        }
+    }
+
+    #[test]
+    fn test_kind_names_round_trip() {
+        for kind in RandomKind::ALL {
+            assert_eq!(kind.name().parse::<RandomKind>(), Ok(kind));
+        }
+        assert_eq!("dice".parse::<RandomKind>(), Err(UnknownRandomKind));
+    }
+}
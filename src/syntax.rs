@@ -0,0 +1,348 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::cpu::{AddType, Instruction, JPType, LDType, SEType};
+
+// the notations instructions can be printed in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    // the mnemonics from Cowgod's technical reference, `LD V1, 0x20`.
+    // this is what Instruction's Display writes and FromStr reads
+    #[default]
+    Cowgod,
+    // Octo assembly, `v1 := 0x20`
+    Octo,
+    // a plain english description, `set V1 to 0x20`
+    English,
+}
+
+impl Syntax {
+    pub const ALL: [Syntax; 3] = [Syntax::Cowgod, Syntax::Octo, Syntax::English];
+
+    // short name used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cowgod => "cowgod",
+            Self::Octo => "octo",
+            Self::English => "english",
+        }
+    }
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownSyntax;
+
+impl fmt::Display for UnknownSyntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unknown syntax, expected one of cowgod, octo, english")
+    }
+}
+
+impl core::error::Error for UnknownSyntax {}
+
+impl FromStr for Syntax {
+    type Err = UnknownSyntax;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Syntax::ALL
+            .into_iter()
+            .find(|syntax| syntax.name().eq_ignore_ascii_case(s))
+            .ok_or(UnknownSyntax)
+    }
+}
+
+// an instruction printed in a given syntax, see Instruction::display
+pub struct Formatted<'a> {
+    instruction: &'a Instruction,
+    syntax: Syntax,
+    // the address word that follows an XO-CHIP long load
+    long: Option<u16>,
+}
+
+impl Instruction {
+    pub fn display(&self, syntax: Syntax) -> Formatted<'_> {
+        Formatted {
+            instruction: self,
+            syntax,
+            long: None,
+        }
+    }
+}
+
+impl Formatted<'_> {
+    // the word after a long load is its operand. without it Octo can only
+    // be given the F000 opcode as raw bytes, followed by the word as
+    // whatever it decodes to
+    pub fn with_long(self, long: Option<u16>) -> Self {
+        Formatted { long, ..self }
+    }
+}
+
+impl fmt::Display for Formatted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.syntax {
+            Syntax::Cowgod => write!(f, "{}", self.instruction),
+            Syntax::Octo => write_octo(f, self.instruction, self.long),
+            Syntax::English => write_english(f, self.instruction),
+        }
+    }
+}
+
+// Octo has no mnemonics for the CHIP-8X and MegaChip extensions, those are
+// written as the raw opcode bytes, which it assembles as they are
+fn write_octo(
+    f: &mut fmt::Formatter<'_>,
+    instruction: &Instruction,
+    long: Option<u16>,
+) -> fmt::Result {
+    match *instruction {
+        Instruction::CLS => write!(f, "clear"),
+        Instruction::RET => write!(f, "return"),
+        Instruction::SYS(nnn) => write!(f, "native {:#x}", nnn),
+        Instruction::JP(JPType::Addr(nnn)) => write!(f, "jump {:#x}", nnn),
+        Instruction::JP(JPType::FromV0(nnn)) => write!(f, "jump0 {:#x}", nnn),
+        Instruction::CALL(nnn) => write!(f, ":call {:#x}", nnn),
+        // a skip is the inverse of the condition Octo's `if` runs under
+        Instruction::SE(x, SEType::Byte(kk)) => write!(f, "if v{:x} != {:#x} then", x, kk),
+        Instruction::SE(x, SEType::Reg(y)) => write!(f, "if v{:x} != v{:x} then", x, y),
+        Instruction::SNE(x, SEType::Byte(kk)) => write!(f, "if v{:x} == {:#x} then", x, kk),
+        Instruction::SNE(x, SEType::Reg(y)) => write!(f, "if v{:x} == v{:x} then", x, y),
+        Instruction::SKP(x) => write!(f, "if v{:x} -key then", x),
+        Instruction::SKNP(x) => write!(f, "if v{:x} key then", x),
+        Instruction::LD(x, LDType::Byte(kk)) => write!(f, "v{:x} := {:#x}", x, kk),
+        Instruction::LD(x, LDType::Reg(y)) => write!(f, "v{:x} := v{:x}", x, y),
+        Instruction::LD(_, LDType::Addr(nnn)) => write!(f, "i := {:#x}", nnn),
+        Instruction::LD(_, LDType::LongAddr) => match long {
+            Some(long) => write!(f, "i := long {:#x}", long),
+            None => write_raw(f, instruction),
+        },
+        Instruction::LD(x, LDType::FromDT) => write!(f, "v{:x} := delay", x),
+        Instruction::LD(x, LDType::KeyPress) => write!(f, "v{:x} := key", x),
+        Instruction::LD(x, LDType::ToDT) => write!(f, "delay := v{:x}", x),
+        Instruction::LD(x, LDType::ToST) => write!(f, "buzzer := v{:x}", x),
+        Instruction::LD(x, LDType::F) => write!(f, "i := hex v{:x}", x),
+        Instruction::LD(x, LDType::HF) => write!(f, "i := bighex v{:x}", x),
+        Instruction::LD(x, LDType::B) => write!(f, "bcd v{:x}", x),
+        Instruction::LD(x, LDType::ToI) => write!(f, "save v{:x}", x),
+        Instruction::LD(x, LDType::FromI) => write!(f, "load v{:x}", x),
+        Instruction::LD(x, LDType::ToR) => write!(f, "saveflags v{:x}", x),
+        Instruction::LD(x, LDType::FromR) => write!(f, "loadflags v{:x}", x),
+        Instruction::LD(x, LDType::ToIRange(y)) => write!(f, "save v{:x} - v{:x}", x, y),
+        Instruction::LD(x, LDType::FromIRange(y)) => write!(f, "load v{:x} - v{:x}", x, y),
+        Instruction::ADD(x, AddType::Byte(kk)) => write!(f, "v{:x} += {:#x}", x, kk),
+        Instruction::ADD(x, AddType::Reg(y)) => write!(f, "v{:x} += v{:x}", x, y),
+        Instruction::ADD(x, AddType::I) => write!(f, "i += v{:x}", x),
+        Instruction::OR(x, y) => write!(f, "v{:x} |= v{:x}", x, y),
+        Instruction::AND(x, y) => write!(f, "v{:x} &= v{:x}", x, y),
+        Instruction::XOR(x, y) => write!(f, "v{:x} ^= v{:x}", x, y),
+        Instruction::SUB(x, y) => write!(f, "v{:x} -= v{:x}", x, y),
+        Instruction::SHR(x, y) => write!(f, "v{:x} >>= v{:x}", x, y),
+        Instruction::SUBN(x, y) => write!(f, "v{:x} =- v{:x}", x, y),
+        Instruction::SHL(x, y) => write!(f, "v{:x} <<= v{:x}", x, y),
+        Instruction::RND(x, kk) => write!(f, "v{:x} := random {:#x}", x, kk),
+        Instruction::DRW(x, y, n) => write!(f, "sprite v{:x} v{:x} {:#x}", x, y, n),
+        Instruction::SCD(n) => write!(f, "scroll-down {:#x}", n),
+        Instruction::SCU(n) => write!(f, "scroll-up {:#x}", n),
+        Instruction::SCR => write!(f, "scroll-right"),
+        Instruction::SCL => write!(f, "scroll-left"),
+        Instruction::EXIT => write!(f, "exit"),
+        Instruction::LOW => write!(f, "lores"),
+        Instruction::HIGH => write!(f, "hires"),
+        Instruction::PLANE(n) => write!(f, "plane {:#x}", n),
+        Instruction::AUDIO => write!(f, "audio"),
+        Instruction::PITCH(x) => write!(f, "pitch := v{:x}", x),
+        _ => write_raw(f, instruction),
+    }
+}
+
+fn write_raw(f: &mut fmt::Formatter<'_>, instruction: &Instruction) -> fmt::Result {
+    let [hi, lo] = instruction.encode().to_be_bytes();
+    write!(f, "{:#04x} {:#04x}", hi, lo)
+}
+
+fn write_english(f: &mut fmt::Formatter<'_>, instruction: &Instruction) -> fmt::Result {
+    match *instruction {
+        Instruction::RAW0 => write!(f, "stop, this is zeroed memory"),
+        Instruction::CLS => write!(f, "clear the screen"),
+        Instruction::RET => write!(f, "return from the subroutine"),
+        Instruction::SYS(nnn) => write!(f, "run the machine code at {:#x}", nnn),
+        Instruction::JP(JPType::Addr(nnn)) => write!(f, "jump to {:#x}", nnn),
+        Instruction::JP(JPType::FromV0(nnn)) => write!(f, "jump to {:#x} plus V0", nnn),
+        Instruction::CALL(nnn) => write!(f, "call the subroutine at {:#x}", nnn),
+        Instruction::SE(x, SEType::Byte(kk)) => {
+            write!(f, "skip the next instruction if V{:X} is {:#x}", x, kk)
+        }
+        Instruction::SE(x, SEType::Reg(y)) => {
+            write!(f, "skip the next instruction if V{:X} equals V{:X}", x, y)
+        }
+        Instruction::SNE(x, SEType::Byte(kk)) => {
+            write!(f, "skip the next instruction unless V{:X} is {:#x}", x, kk)
+        }
+        Instruction::SNE(x, SEType::Reg(y)) => write!(
+            f,
+            "skip the next instruction unless V{:X} equals V{:X}",
+            x, y
+        ),
+        Instruction::SKP(x) => write!(
+            f,
+            "skip the next instruction if the key in V{:X} is down",
+            x
+        ),
+        Instruction::SKNP(x) => write!(
+            f,
+            "skip the next instruction unless the key in V{:X} is down",
+            x
+        ),
+        Instruction::SKP2(x) => write!(
+            f,
+            "skip the next instruction if the key in V{:X} is down on keypad 2",
+            x
+        ),
+        Instruction::SKNP2(x) => write!(
+            f,
+            "skip the next instruction unless the key in V{:X} is down on keypad 2",
+            x
+        ),
+        Instruction::LD(x, LDType::Byte(kk)) => write!(f, "set V{:X} to {:#x}", x, kk),
+        Instruction::LD(x, LDType::Reg(y)) => write!(f, "set V{:X} to V{:X}", x, y),
+        Instruction::LD(_, LDType::Addr(nnn)) => write!(f, "point I at {:#x}", nnn),
+        Instruction::LD(_, LDType::LongAddr) => {
+            write!(f, "point I at the address in the next two bytes")
+        }
+        Instruction::LD(x, LDType::FromDT) => write!(f, "set V{:X} to the delay timer", x),
+        Instruction::LD(x, LDType::KeyPress) => write!(f, "wait for a key and put it in V{:X}", x),
+        Instruction::LD(x, LDType::ToDT) => write!(f, "set the delay timer to V{:X}", x),
+        Instruction::LD(x, LDType::ToST) => write!(f, "set the sound timer to V{:X}", x),
+        Instruction::LD(x, LDType::F) => write!(f, "point I at the small font digit in V{:X}", x),
+        Instruction::LD(x, LDType::HF) => write!(f, "point I at the big font digit in V{:X}", x),
+        Instruction::LD(x, LDType::B) => write!(f, "store the decimal digits of V{:X} at I", x),
+        Instruction::LD(x, LDType::ToI) => write!(f, "store V0 to V{:X} at I", x),
+        Instruction::LD(x, LDType::FromI) => write!(f, "load V0 to V{:X} from I", x),
+        Instruction::LD(x, LDType::ToR) => write!(f, "store V0 to V{:X} in the flags", x),
+        Instruction::LD(x, LDType::FromR) => write!(f, "load V0 to V{:X} from the flags", x),
+        Instruction::LD(x, LDType::ToIRange(y)) => write!(f, "store V{:X} to V{:X} at I", x, y),
+        Instruction::LD(x, LDType::FromIRange(y)) => write!(f, "load V{:X} to V{:X} from I", x, y),
+        Instruction::ADD(x, AddType::Byte(kk)) => write!(f, "add {:#x} to V{:X}", kk, x),
+        Instruction::ADD(x, AddType::Reg(y)) => write!(f, "add V{:X} to V{:X}, VF = carry", y, x),
+        Instruction::ADD(x, AddType::I) => write!(f, "add V{:X} to I", x),
+        Instruction::OR(x, y) => write!(f, "set V{:X} to V{:X} or V{:X}", x, x, y),
+        Instruction::AND(x, y) => write!(f, "set V{:X} to V{:X} and V{:X}", x, x, y),
+        Instruction::XOR(x, y) => write!(f, "set V{:X} to V{:X} xor V{:X}", x, x, y),
+        Instruction::SUB(x, y) => write!(f, "subtract V{:X} from V{:X}, VF = no borrow", y, x),
+        Instruction::SUBN(x, y) => {
+            write!(f, "set V{:X} to V{:X} minus V{:X}, VF = no borrow", x, y, x)
+        }
+        Instruction::SHR(x, y) => write!(f, "shift V{:X} (or V{:X}) right into V{:X}", x, y, x),
+        Instruction::SHL(x, y) => write!(f, "shift V{:X} (or V{:X}) left into V{:X}", x, y, x),
+        Instruction::RND(x, kk) => write!(f, "set V{:X} to a random number masked by {:#x}", x, kk),
+        Instruction::DRW(x, y, n) => {
+            write!(f, "draw the {}-row sprite at I at V{:X}, V{:X}", n, x, y)
+        }
+        Instruction::SCD(n) => write!(f, "scroll the screen down {} pixels", n),
+        Instruction::SCU(n) => write!(f, "scroll the screen up {} pixels", n),
+        Instruction::SCR => write!(f, "scroll the screen right 4 pixels"),
+        Instruction::SCL => write!(f, "scroll the screen left 4 pixels"),
+        Instruction::EXIT => write!(f, "exit the interpreter"),
+        Instruction::LOW => write!(f, "switch to low resolution"),
+        Instruction::HIGH => write!(f, "switch to high resolution"),
+        Instruction::PLANE(n) => write!(f, "draw to bitplanes {:#x}", n),
+        Instruction::AUDIO => write!(f, "load the audio pattern from I"),
+        Instruction::PITCH(x) => write!(f, "set the audio pitch to V{:X}", x),
+        Instruction::BGC => write!(f, "cycle the background colour"),
+        Instruction::COL(x, y, n) => write!(
+            f,
+            "colour the zones at V{:X}, V{:X} with V{:X}, {} rows",
+            x,
+            y,
+            (x + 1) & 0xF,
+            n
+        ),
+        Instruction::MEGAOFF => write!(f, "leave MegaChip mode"),
+        Instruction::MEGAON => write!(f, "enter MegaChip mode"),
+        Instruction::LDHI(nn) => write!(
+            f,
+            "point I at {:#x} shifted 16 left plus the next two bytes",
+            nn
+        ),
+        Instruction::LDPAL(nn) => write!(f, "load {} palette colours from I", nn),
+        Instruction::SPRW(nn) => write!(f, "set the sprite width to {}", nn),
+        Instruction::SPRH(nn) => write!(f, "set the sprite height to {}", nn),
+        Instruction::ALPHA(nn) => write!(f, "set the screen alpha to {:#x}", nn),
+        Instruction::DIGISND(n) => {
+            write!(f, "play the sample at I, looping unless {} is nonzero", n)
+        }
+        Instruction::STOPSND => write!(f, "stop the sample"),
+        Instruction::BMODE(n) => write!(f, "set the blend mode to {}", n),
+        Instruction::CCOL(nn) => write!(f, "collide with palette colour {:#x}", nn),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for syntax in Syntax::ALL {
+            assert_eq!(syntax.name().parse::<Syntax>(), Ok(syntax));
+        }
+        assert_eq!("intel".parse::<Syntax>(), Err(UnknownSyntax));
+    }
+
+    #[test]
+    fn test_dialects() {
+        let cases = [
+            (
+                0x8126,
+                "SHR V1 {, V2}",
+                "v1 >>= v2",
+                "shift V1 (or V2) right into V1",
+            ),
+            (0xA200, "LD I, 0x200", "i := 0x200", "point I at 0x200"),
+            (
+                0x3A12,
+                "SE VA, 0x12",
+                "if va != 0x12 then",
+                "skip the next instruction if VA is 0x12",
+            ),
+            (
+                0xB300,
+                "JP V0, 0x300",
+                "jump0 0x300",
+                "jump to 0x300 plus V0",
+            ),
+            (0xF31E, "ADD I, V3", "i += v3", "add V3 to I"),
+        ];
+        for (opcode, cowgod, octo, english) in cases {
+            let ins = Instruction::decode(opcode).unwrap();
+            assert_eq!(ins.display(Syntax::Cowgod).to_string(), cowgod);
+            assert_eq!(ins.display(Syntax::Octo).to_string(), octo);
+            assert_eq!(ins.display(Syntax::English).to_string(), english);
+        }
+    }
+
+    #[test]
+    fn test_octo_raw_bytes() {
+        assert_eq!(
+            Instruction::BGC.display(Syntax::Octo).to_string(),
+            "0x02 0xa0"
+        );
+    }
+
+    #[test]
+    fn test_octo_long_load() {
+        let ins = Instruction::LD(0, LDType::LongAddr);
+        assert_eq!(ins.display(Syntax::Octo).to_string(), "0xf0 0x00");
+        assert_eq!(
+            ins.display(Syntax::Octo).with_long(Some(0x1234)).to_string(),
+            "i := long 0x1234"
+        );
+        assert_eq!(ins.display(Syntax::Cowgod).with_long(Some(0x1234)).to_string(), "LD I, LONG");
+    }
+}
@@ -0,0 +1,188 @@
+use crate::cpu::{AddType, Cpu, Instruction, JPType, LDType, SEType};
+use crate::random::RandomSource;
+
+// the VIP's 1802 runs 3668 machine cycles per 60 Hz frame. the CDP1861
+// takes 1024 of them for display dma and the interrupt routine that
+// counts down the timers takes another 46, the rest run the interpreter
+pub const CYCLES_PER_FRAME: u32 = 3668;
+pub const DISPLAY_DMA_CYCLES: u32 = 1024;
+pub const INTERRUPT_CYCLES: u32 = 46;
+pub const INTERPRETER_CYCLES: u32 = CYCLES_PER_FRAME - DISPLAY_DMA_CYCLES - INTERRUPT_CYCLES;
+
+// fetching and dispatching every instruction
+const FETCH_CYCLES: u32 = 40;
+// SE, SNE, SKP and SKNP when they skip
+const SKIP_CYCLES: u32 = 4;
+
+// counts machine cycles in place of instructions per frame, see
+// Cpu::run_frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VipTiming {
+    pub total_cycles: u64,
+    // spent in the current frame, an instruction that overruns the frame
+    // is paid for out of the next one
+    pub frame_cycles: u32,
+}
+
+impl VipTiming {
+    pub fn charge(&mut self, cycles: u32) {
+        self.total_cycles += cycles as u64;
+        self.frame_cycles += cycles;
+    }
+
+    pub fn frame_done(&self) -> bool {
+        self.frame_cycles >= INTERPRETER_CYCLES
+    }
+
+    // the interrupt ends the frame. when the interpreter was waiting for it
+    // (DXYN, FX0A, a halt) the rest of the frame is idle
+    pub fn end_frame(&mut self, waited: bool) {
+        let spent = if waited {
+            INTERPRETER_CYCLES.max(self.frame_cycles)
+        } else {
+            self.frame_cycles
+        };
+        self.total_cycles += (spent - self.frame_cycles) as u64;
+        self.total_cycles += (DISPLAY_DMA_CYCLES + INTERRUPT_CYCLES) as u64;
+        self.frame_cycles = spent - INTERPRETER_CYCLES.min(spent);
+    }
+}
+
+impl<R: RandomSource, const MEMORY: usize> Cpu<R, MEMORY> {
+    // what the VIP interpreter spends on `instruction` given the current
+    // state, in 1802 machine cycles. 0NNN is charged separately for the
+    // machine code it actually runs
+    pub fn instruction_cycles(&self, instruction: &Instruction) -> u32 {
+        let vx = |x: &u8| self.vx[*x as usize];
+        let skip = |taken: bool| if taken { SKIP_CYCLES } else { 0 };
+
+        FETCH_CYCLES
+            + match instruction {
+                // 256 display bytes, 12 cycles each
+                Instruction::CLS => 24 + 256 * 12,
+                Instruction::RET => 10,
+                Instruction::SYS(_) => 0,
+                Instruction::JP(JPType::Addr(_)) => 12,
+                // an extra 2 when the jump crosses a page
+                Instruction::JP(JPType::FromV0(nnn)) => {
+                    let target = nnn + self.vx[0] as u16;
+                    22 + if target & 0xFF00 != nnn & 0xFF00 {
+                        2
+                    } else {
+                        0
+                    }
+                }
+                Instruction::CALL(_) => 26,
+                Instruction::SE(x, SEType::Byte(kk)) => 10 + skip(vx(x) == *kk),
+                Instruction::SE(x, SEType::Reg(y)) => 14 + skip(vx(x) == vx(y)),
+                Instruction::SNE(x, SEType::Byte(kk)) => 10 + skip(vx(x) != *kk),
+                Instruction::SNE(x, SEType::Reg(y)) => 14 + skip(vx(x) != vx(y)),
+                Instruction::LD(_, LDType::Byte(_)) => 6,
+                Instruction::ADD(_, AddType::Byte(_)) => 10,
+                Instruction::LD(_, LDType::Reg(_))
+                | Instruction::OR(..)
+                | Instruction::AND(..)
+                | Instruction::XOR(..)
+                | Instruction::ADD(_, AddType::Reg(_))
+                | Instruction::SUB(..)
+                | Instruction::SHR(..)
+                | Instruction::SUBN(..)
+                | Instruction::SHL(..) => 44,
+                Instruction::LD(_, LDType::Addr(_)) => 12,
+                Instruction::RND(..) => 36,
+                // each row is shifted into place unless the sprite is byte
+                // aligned
+                Instruction::DRW(x, _, n) => {
+                    let row = if vx(x) % 8 == 0 { 24 } else { 46 };
+                    68 + *n as u32 * row
+                }
+                Instruction::SKP(x) => 14 + skip(self.kp[vx(x) as usize & 0xF]),
+                Instruction::SKNP(x) => 14 + skip(!self.kp[vx(x) as usize & 0xF]),
+                Instruction::LD(_, LDType::FromDT)
+                | Instruction::LD(_, LDType::ToDT)
+                | Instruction::LD(_, LDType::ToST) => 10,
+                Instruction::LD(_, LDType::KeyPress) => 18,
+                // an extra 6 when I crosses a page
+                Instruction::ADD(x, AddType::I) => {
+                    12 + if (self.ir & 0xFF) + vx(x) as u16 > 0xFF {
+                        6
+                    } else {
+                        0
+                    }
+                }
+                Instruction::LD(_, LDType::F) => 16,
+                // the digits are found by repeated subtraction
+                Instruction::LD(x, LDType::B) => {
+                    let v = vx(x) as u32;
+                    80 + (v / 100 + v / 10 % 10 + v % 10) * 16
+                }
+                Instruction::LD(x, LDType::ToI) | Instruction::LD(x, LDType::FromI) => {
+                    14 + 14 * (*x as u32 + 1)
+                }
+                // not on the VIP, charged like a register op
+                _ => 44,
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::JPType;
+    use crate::platform::Platform;
+
+    #[test]
+    fn test_instruction_cycles() {
+        let mut cpu = Cpu::for_platform(Platform::CosmacVip);
+        cpu.vx[0x0] = 0xFF;
+        cpu.vx[0x1] = 8;
+        cpu.vx[0x2] = 9;
+        cpu.vx[0x3] = 123;
+
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::LD(0x0, LDType::Byte(1))),
+            46
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::SE(0x1, SEType::Byte(8))),
+            54
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::SE(0x1, SEType::Byte(9))),
+            50
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::DRW(0x1, 0x0, 5)),
+            40 + 68 + 5 * 24
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::DRW(0x2, 0x0, 5)),
+            40 + 68 + 5 * 46
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::LD(0x3, LDType::B)),
+            40 + 80 + 6 * 16
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::JP(JPType::FromV0(0x201))),
+            64
+        );
+        assert_eq!(
+            cpu.instruction_cycles(&Instruction::LD(0x2, LDType::ToI)),
+            40 + 14 + 14 * 3
+        );
+    }
+
+    #[test]
+    fn test_end_frame() {
+        let mut timing = VipTiming::default();
+        timing.charge(INTERPRETER_CYCLES + 10);
+        timing.end_frame(false);
+        assert_eq!(timing.frame_cycles, 10);
+
+        // waiting for the interrupt idles out the rest of the frame
+        timing.end_frame(true);
+        assert_eq!(timing.frame_cycles, 0);
+        assert_eq!(timing.total_cycles, 2 * CYCLES_PER_FRAME as u64);
+    }
+}